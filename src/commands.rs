@@ -1,4 +1,8 @@
+pub(crate) mod config;
+pub(crate) mod export;
 pub(crate) mod farm;
 pub(crate) mod info;
 pub(crate) mod init;
+pub(crate) mod profiles;
+pub(crate) mod service;
 pub(crate) mod wipe;
@@ -0,0 +1,70 @@
+use clap::Subcommand;
+use color_eyre::eyre::{eyre, Context, Result};
+use toml::Value;
+
+use crate::config::keys::{get_value, parse_value, set_value};
+use crate::config::{
+    check_config, config_path, parse_config, parse_config_table, validate_config, write_config,
+    Config,
+};
+
+/// subcommands of the `config` command
+#[derive(Debug, Default, Subcommand)]
+pub(crate) enum ConfigCommand {
+    /// prints the effective config, including environment and `--set` overrides
+    #[default]
+    Show,
+    /// prints the value of the given dotted key (i.e. `farmer.plots.0.size`)
+    Get { key: String },
+    /// validates the value, and writes it to the given dotted key
+    Set { key: String, value: String },
+    /// checks whether the config is valid for farming, including the
+    /// directories and the free space of the plots
+    Validate,
+    /// prints the path of the config file
+    Path,
+}
+
+/// implementation of the `config` command
+///
+/// shows, edits and validates the config file created by `init`
+pub(crate) fn config(command: ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Show => {
+            let config = parse_config()?;
+            print!("{}", toml::to_string_pretty(&config).context("Failed to serialize config")?);
+        }
+        ConfigCommand::Get { key } => {
+            let config = Value::try_from(parse_config()?).context("Failed to serialize config")?;
+            match get_value(&config, &key) {
+                Some(Value::String(value)) => println!("{value}"),
+                Some(Value::Table(table)) => print!(
+                    "{}",
+                    toml::to_string_pretty(table).context("Failed to serialize config")?
+                ),
+                Some(value) => println!("{value}"),
+                None => return Err(eyre!("`{key}` is not set in the config")),
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            let mut table = Value::Table(parse_config_table()?);
+            set_value(&mut table, &key, parse_value(&key, &value)?)?;
+
+            let config: Config = table
+                .try_into()
+                .wrap_err_with(|| format!("config would be invalid after setting `{key}`"))?;
+            check_config(&config)
+                .wrap_err_with(|| format!("config would be invalid after setting `{key}`"))?;
+
+            write_config(&config)?;
+            println!("`{key}` is set to `{value}`");
+        }
+        ConfigCommand::Validate => {
+            validate_config().context("config is not valid")?;
+            println!("Config at `{}` is valid!", config_path().display());
+        }
+        ConfigCommand::Path => println!("{}", config_path().display()),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use clap::{Args, Subcommand, ValueEnum};
+use color_eyre::eyre::{eyre, Context, Result};
+use serde::Serialize;
+use subspace_sdk::node::BlockNumber;
+
+use crate::config::parse_config;
+use crate::summary::history::date_timestamp;
+use crate::summary::ledger::{query_ledger, LedgerRecord, RewardKind};
+use crate::summary::storage::RecordQuery;
+use crate::summary::{active_summary_dir, summary_dir, SummaryFile, Token, Units};
+use crate::utils::date_parser;
+
+/// subcommands of the `export` command
+#[derive(Debug, Subcommand)]
+pub(crate) enum ExportCommand {
+    /// exports every reward of the farmer, from the reward ledger
+    Rewards(ExportRewardsArgs),
+}
+
+impl Default for ExportCommand {
+    fn default() -> Self {
+        ExportCommand::Rewards(ExportRewardsArgs::default())
+    }
+}
+
+/// arguments of the `export rewards` command
+#[derive(Debug, Default, Args)]
+pub(crate) struct ExportRewardsArgs {
+    /// format of the export
+    #[arg(long, value_enum, default_value_t)]
+    format: ExportFormat,
+    /// only exports the rewards from the given date (`YYYY-MM-DD`, in UTC) or
+    /// block number
+    #[arg(long, value_parser = bound_parser)]
+    from: Option<Bound>,
+    /// only exports the rewards until the given date (`YYYY-MM-DD`, in UTC) or
+    /// block number, inclusive
+    #[arg(long, value_parser = bound_parser)]
+    to: Option<Bound>,
+    /// file to write the export to, instead of the standard output
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+/// format of the export
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+/// bound of the exported range, either a date or a block number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bound {
+    Date(NaiveDate),
+    Block(BlockNumber),
+}
+
+/// parses a block number, or a date in the `YYYY-MM-DD` format
+pub(crate) fn bound_parser(bound: &str) -> Result<Bound> {
+    if bound.chars().all(|char| char.is_ascii_digit()) {
+        return BlockNumber::from_str(bound)
+            .map(Bound::Block)
+            .map_err(|err| eyre!("could not parse the block number: {err}"));
+    }
+    date_parser(bound).map(Bound::Date)
+}
+
+/// a row of the export
+#[derive(Debug, Serialize)]
+pub(crate) struct RewardRow {
+    /// unknown if the config is gone (i.e. wiped)
+    reward_address: Option<String>,
+    block_number: BlockNumber,
+    block_hash: String,
+    /// in the RFC 3339 format, in UTC
+    timestamp: Option<String>,
+    kind: String,
+    /// amounts are strings, so that they are exact, in the smallest unit of
+    /// the token
+    amount: String,
+    /// in whole tokens, i.e. SSC
+    amount_ssc: String,
+    /// symbol of the token of `amount_ssc`
+    symbol: String,
+}
+
+impl RewardRow {
+    /// header of the CSV export
+    const CSV_HEADER: &'static str =
+        "reward_address,block_number,block_hash,timestamp,kind,amount,amount_ssc,symbol";
+
+    /// returns the row as a CSV record
+    pub(crate) fn to_csv(&self) -> String {
+        let RewardRow {
+            reward_address,
+            block_number,
+            block_hash,
+            timestamp,
+            kind,
+            amount,
+            amount_ssc,
+            symbol,
+        } = self;
+        let reward_address = reward_address.as_deref().unwrap_or_default();
+        let timestamp = timestamp.as_deref().unwrap_or_default();
+        format!(
+            "{reward_address},{block_number},{block_hash},{timestamp},{kind},{amount},\
+             {amount_ssc},{symbol}"
+        )
+    }
+}
+
+/// implementation of the `export` command
+///
+/// exports the records of the reward ledger, i.e. for accounting
+pub(crate) async fn export(command: ExportCommand) -> Result<()> {
+    match command {
+        ExportCommand::Rewards(args) => export_rewards(args).await,
+    }
+}
+
+/// exports the rewards of the farmer, in the given range
+async fn export_rewards(
+    ExportRewardsArgs { format, from, to, out }: ExportRewardsArgs,
+) -> Result<()> {
+    // the config may be gone (i.e. wiped), in which case the default locations are
+    // used
+    let config = parse_config().ok();
+    let summary_dir = active_summary_dir(
+        &config.as_ref().map_or_else(summary_dir, |config| config.farmer.summary_directory.clone()),
+    );
+    // summaries of the older versions do not know their reward address
+    let summary = SummaryFile::new(&summary_dir, None)
+        .await
+        .context("couldn't parse summary file, are you sure you have ran `farm` command?")?
+        .parse()
+        .await?;
+    let token = summary.token.unwrap_or_default();
+    let reward_address = match summary.key {
+        Some(key) => Some(key.reward_address),
+        None => config.map(|config| config.farmer.reward_address.to_string()),
+    };
+
+    let records = query_ledger(&summary_dir, record_query(from, to))
+        .await
+        .context("couldn't read the reward ledger, are you sure you have ran `farm` command?")?;
+    let rows = records
+        .into_iter()
+        .filter(|record| record.kind != RewardKind::Vote)
+        .map(|record| reward_row(reward_address.clone(), &token, record))
+        .collect::<Vec<_>>();
+
+    let export = match format {
+        ExportFormat::Csv => std::iter::once(RewardRow::CSV_HEADER.to_owned())
+            .chain(rows.iter().map(RewardRow::to_csv))
+            .map(|line| line + "\n")
+            .collect::<String>(),
+        ExportFormat::Json =>
+            serde_json::to_string_pretty(&rows).context("Failed to serialize the rewards")? + "\n",
+    };
+
+    match out {
+        Some(out) => {
+            std::fs::write(&out, export)
+                .wrap_err_with(|| format!("couldn't write the export to `{}`", out.display()))?;
+            println!("Exported {} reward(s) to `{}`", rows.len(), out.display());
+        }
+        None => std::io::stdout()
+            .write_all(export.as_bytes())
+            .context("couldn't write the export to the standard output")?,
+    }
+
+    Ok(())
+}
+
+/// returns the query of the records in the given range
+///
+/// the date of records without a timestamp (i.e. of pruned blocks) is unknown,
+/// so they are not in any range of dates
+pub(crate) fn record_query(from: Option<Bound>, to: Option<Bound>) -> RecordQuery {
+    let mut query = RecordQuery::default();
+    match from {
+        None => (),
+        Some(Bound::Block(from)) => query.from_block = Some(from),
+        Some(Bound::Date(from)) => query.from_timestamp = Some(date_timestamp(from)),
+    }
+    match to {
+        None => (),
+        Some(Bound::Block(to)) => query.to_block = Some(to),
+        // until the end of the day
+        Some(Bound::Date(to)) =>
+            query.to_timestamp = to.succ_opt().map(|next| date_timestamp(next) - 1),
+    }
+    query
+}
+
+/// converts a record of the ledger into a row of the export, with its amount in
+/// both the smallest unit of the token and whole tokens
+pub(crate) fn reward_row(
+    reward_address: Option<String>,
+    token: &Token,
+    record: LedgerRecord,
+) -> RewardRow {
+    let LedgerRecord { block_number, block_hash, timestamp, kind, amount } = record;
+    let timestamp = timestamp
+        .and_then(|timestamp| i64::try_from(timestamp).ok())
+        .and_then(|timestamp| chrono::NaiveDateTime::from_timestamp_opt(timestamp, 0))
+        .map(|date_time| date_time.format("%Y-%m-%dT%H:%M:%SZ").to_string());
+
+    RewardRow {
+        reward_address,
+        block_number,
+        block_hash,
+        timestamp,
+        kind: kind.to_string(),
+        amount: token.amount(amount, Units::Base),
+        amount_ssc: token.amount(amount, Units::Ssc),
+        symbol: token.symbol.clone(),
+    }
+}
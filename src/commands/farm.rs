@@ -1,30 +1,47 @@
 use std::io::Write;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use color_eyre::eyre::{eyre, Context, Error, Result};
 use futures::prelude::*;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use owo_colors::OwoColorize;
 use single_instance::SingleInstance;
-use subspace_sdk::node::{Event, Hash, RewardsEvent, SubspaceEvent, SyncingProgress};
+use subspace_sdk::node::{BlockNumber, Event, Hash, RewardsEvent, SubspaceEvent, SyncingProgress};
 use subspace_sdk::{Farmer, Node, PublicKey};
 use tokio::signal;
+use tokio::sync::Notify;
 use tokio::task::JoinHandle;
 use tracing::instrument;
 
-use crate::config::{validate_config, ChainConfig, Config};
-use crate::summary::{Rewards, Summary, SummaryFile, SummaryUpdateFields};
+use crate::config::keys::get_value;
+use crate::config::layers::ConfigSources;
+use crate::config::{
+    is_original_config, validate_config, ChainConfig, Config, MetricsConfig, StatusConfig,
+};
+use crate::control::{listen, Control};
+use crate::metrics::{serve_metrics, Metrics};
+use crate::output::{emit, output_format, print_line, set_output_format, FarmEvent, OutputFormat};
+use crate::profile::profile;
+use crate::status::{serve_status, Status};
+use crate::summary::ledger::{read_ledger, Ledger, LedgerRecord, LedgerTotals, RewardKind};
+use crate::summary::storage::{has_ledger, migrate_storage};
+use crate::summary::{
+    activate_summary, ProcessedBlock, Rewards, Summary, SummaryFile, SummaryKey,
+    SummaryUpdateFields, Token, Units,
+};
+use crate::systemd::{heartbeat_interval, keep_alive, notify_alive, notify_ready, notify_stopping};
 use crate::utils::{
-    install_tracing, raise_fd_limit, spawn_task, IntoEyre, IntoEyreFuture, IntoEyreStream,
+    control_socket_getter, install_tracing, raise_fd_limit, spawn_task, IntoEyre, IntoEyreFuture,
+    IntoEyreStream,
 };
 
-/// allows us to detect multiple instances of the farmer and act on it
-pub(crate) const SINGLE_INSTANCE: &str = ".subspaceFarmer";
-const BATCH_BLOCKS: usize = 1000;
+/// the summary is saved after every batch, so a crash loses at most a batch
+const BATCH_BLOCKS: usize = 100;
 const N_TASKS: usize = 10;
 
-type MaybeHandles = Option<(JoinHandle<Result<()>>, JoinHandle<Result<()>>)>;
+type Handles = (JoinHandle<Result<()>>, JoinHandle<Result<()>>);
 
 /// implementation of the `farm` command
 ///
@@ -32,35 +49,54 @@ type MaybeHandles = Option<(JoinHandle<Result<()>>, JoinHandle<Result<()>>)>;
 ///
 /// first, checks for an existing farmer instance
 /// then starts the farming and node instances,
-/// lastly, depending on the verbosity, it subscribes to plotting progress and
-/// new solutions
+/// lastly, it subscribes to plotting progress and new solutions, which are
+/// drawn or emitted depending on the `output` (and never drawn with
+/// `--verbose`)
 #[instrument]
-pub(crate) async fn farm(is_verbose: bool, executor: bool) -> Result<()> {
+pub(crate) async fn farm(
+    is_verbose: bool,
+    executor: bool,
+    output: Option<OutputFormat>,
+) -> Result<()> {
+    set_output_format(OutputFormat::select(output, is_verbose));
     install_tracing(is_verbose);
     color_eyre::install()
         .context("color eyre installment failed, it should have been the first one")?;
 
-    let instance = SingleInstance::new(SINGLE_INSTANCE)
+    let profile = profile();
+    let instance = SingleInstance::new(&profile.instance_lock())
         .context("Cannot take the instance lock from the OS! Aborting...")?;
     if !instance.is_single() {
         return Err(eyre!(
-            "It seems like there is already a farming instance running. Aborting...",
+            "It seems like there is already a farming instance running for the `{}` profile. \
+             Aborting...",
+            profile.name()
         ));
     }
     // raise file limit
     raise_fd_limit();
 
-    let Config { chain, farmer: farmer_config, node: mut node_config } =
-        validate_config().context("couldn't validate config")?;
+    let (config, sources) = validate_config().context("couldn't validate config")?;
+    if is_verbose {
+        print_config_sources(&config, &sources)?;
+    }
+    let Config {
+        chain,
+        farmer: farmer_config,
+        node: mut node_config,
+        metrics: metrics_config,
+        status: status_config,
+        ..
+    } = config;
     let reward_address = farmer_config.reward_address;
 
     // apply advanced options (flags)
     if executor {
-        println!("Setting the {} flag for the node...", "executor".underline());
+        print_line(format!("Setting the {} flag for the node...", "executor".underline()));
         node_config.advanced.executor = true;
     }
 
-    println!("Starting node ...");
+    print_line("Starting node ...");
     let node = Arc::new(
         node_config
             .clone()
@@ -68,25 +104,82 @@ pub(crate) async fn farm(is_verbose: bool, executor: bool) -> Result<()> {
             .await
             .context("error building the node")?,
     );
-    println!("Node started successfully!");
+    print_line("Node started successfully!");
+
+    // the status and the metrics are updated by the subscriptions
+    let metrics = Metrics::new()?;
+    let status = Status::new(metrics.clone());
+    if let Some(MetricsConfig { address }) = metrics_config {
+        spawn_server("metrics_server", serve_metrics(address, metrics)?);
+        print_line(format!("Serving the metrics at http://{address}/metrics"));
+    }
+    if let Some(StatusConfig { address }) = status_config {
+        spawn_server("status_server", serve_status(address, status.clone())?);
+        print_line(format!("Serving the status at http://{address}/status"));
+    }
+    // the progress bars would be mixed with the events of the JSON output, and
+    // with the logs of `--verbose`
+    let is_progress_hidden = is_verbose || output_format() == OutputFormat::Json;
+
+    // the other commands talk to the farm through the control socket, farming goes
+    // on without it
+    let stop = Arc::new(Notify::new());
+    let _control_socket =
+        match listen(&control_socket_getter(), Control::new(status.clone(), stop.clone())) {
+            Ok((control_socket, server)) => {
+                spawn_server("control_server", server);
+                Some(control_socket)
+            }
+            Err(err) => {
+                print_line(format!("{} {err:#}", "warning:".yellow()));
+                None
+            }
+        };
+
+    // the watchdog of systemd is pinged from the start, as syncing takes a while
+    let startup_heartbeat = spawn_task("startup_heartbeat", keep_alive(status.clone()));
 
     if !matches!(chain, ChainConfig::Dev) {
-        if !is_verbose {
-            subscribe_to_node_syncing(&node).await.context("couldn't subscribe to syncing")?;
-        } else {
-            node.sync().await.into_eyre().context("Node syncing failed")?;
-        }
+        subscribe_to_node_syncing(&node, &status, is_progress_hidden)
+            .await
+            .context("couldn't subscribe to syncing")?;
     }
-
-    let summary_file = SummaryFile::new(Some(farmer_config.plot_size))
+    status.set_synced();
+
+    // rewards are only counted for the chain and the reward address they are earned
+    // for, so each of them has its own summary
+    let node_info = node.get_info().await.into_eyre().context("failed to get node info")?;
+    status.set_chain(node_info.chain.clone());
+    status.set_connected_peers(node_info.connected_peers);
+    let summary_key = SummaryKey {
+        chain: node_info.chain,
+        genesis_hash: format!("{:?}", node_info.genesis_hash),
+        reward_address: reward_address.to_string(),
+    };
+    let token = Token { decimals: node_info.token_decimals, symbol: node_info.token_symbol };
+    let is_original_key = is_original_config(&chain, &reward_address);
+    let summary_dir =
+        activate_summary(&farmer_config.summary_directory, &summary_key, is_original_key)
+            .context("couldn't select the summary")?;
+    migrate_storage(&summary_dir, farmer_config.summary_storage)
+        .await
+        .context("couldn't migrate the summary")?;
+    let summary_file = SummaryFile::new(&summary_dir, Some(farmer_config.total_plot_size()))
         .await
         .context("constructing new SummaryFile failed")?;
+    summary_file.set_key(summary_key, token).await.context("couldn't update the summary")?;
+    let ledger = open_ledger(&summary_dir, &summary_file)
+        .await
+        .context("couldn't open the reward ledger")?;
 
-    println!("Starting farmer ...");
+    print_line("Starting farmer ...");
     let farmer = Arc::new(farmer_config.build(&node).await.context("farmer couldn't be build")?);
-    println!("Farmer started successfully!");
+    status.set_farmer_built();
+    print_line("Farmer started successfully!");
+    startup_heartbeat.abort();
+    notify_ready(&status);
 
-    let maybe_handles = if !is_verbose {
+    let handles = {
         // we need this to handle errors when block is not found
         // if this fails, it might be due to: https://github.com/toml-rs/toml/issues/405 and https://github.com/toml-rs/toml/issues/329
         let blocks_pruning =
@@ -98,6 +191,11 @@ pub(crate) async fn farm(is_verbose: bool, executor: bool) -> Result<()> {
 
         // this will be shared between the two subscriptions
         let is_initial_progress_finished = Arc::new(AtomicBool::new(false));
+        // the progress bars of the two subscriptions are drawn together
+        let progress_bars = match is_progress_hidden {
+            true => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+            false => MultiProgress::new(),
+        };
         let sector_size_bytes =
             farmer.get_info().await.into_eyre().context("Failed to get farmer into")?.sector_size;
 
@@ -108,67 +206,135 @@ pub(crate) async fn farm(is_verbose: bool, executor: bool) -> Result<()> {
                 farmer.clone(),
                 is_initial_progress_finished.clone(),
                 sector_size_bytes,
+                progress_bars.clone(),
+                status.clone(),
             ),
         );
 
-        let solution_sub_handle = spawn_task(
-            "solution_subscriber",
-            subscribe_to_solutions(
-                summary_file.clone(),
-                node.clone(),
-                is_initial_progress_finished.clone(),
-                reward_address,
-                blocks_pruning,
-            ),
+        let solution_subscription = subscribe_to_solutions(
+            summary_file.clone(),
+            ledger,
+            node.clone(),
+            is_initial_progress_finished.clone(),
+            reward_address,
+            blocks_pruning,
+            progress_bars,
+            status.clone(),
         );
-
-        Some((plotting_sub_handle, solution_sub_handle))
-    } else {
-        // we don't have handles if it is verbose
-        None
+        let solution_status = status.clone();
+        let solution_sub_handle = spawn_task("solution_subscriber", async move {
+            let result = solution_subscription.await;
+            solution_status.set_solution_subscription_ended();
+            result
+        });
+
+        (plotting_sub_handle, solution_sub_handle)
     };
 
-    wait_on_farmer(maybe_handles, farmer, node).await.context("waiting on farmer failed")?;
+    wait_on_farmer(handles, farmer, node, stop, status)
+        .await
+        .context("waiting on farmer failed")?;
+
+    Ok(())
+}
+
+/// spawns the given local server, which only logs its errors as farming goes
+/// on without it
+fn spawn_server(name: &str, server: impl Future<Output = Result<()>> + Send + 'static) {
+    spawn_task(name, async move {
+        if let Err(err) = server.await {
+            tracing::error!("{err:?}");
+        }
+    });
+}
+
+/// prints the effective value of every config entry, and the layer it comes
+/// from
+fn print_config_sources(config: &Config, sources: &ConfigSources) -> Result<()> {
+    let config = toml::Value::try_from(config).context("Failed to serialize config")?;
+
+    print_line("Using the config:");
+    for (key, source) in sources {
+        if let Some(value) = get_value(&config, key) {
+            print_line(format!("  {key} = {value} ({source})"));
+        }
+    }
 
     Ok(())
 }
 
+/// opens the reward ledger, and checks whether it is consistent with the
+/// summary
+async fn open_ledger(summary_dir: &Path, summary_file: &SummaryFile) -> Result<Ledger> {
+    let is_new_ledger = !has_ledger(summary_dir);
+    let ledger = Ledger::open(summary_dir).await?;
+    let summary = summary_file.parse().await.context("parsing the summary failed")?;
+
+    if is_new_ledger && summary.last_processed_block_num > 0 {
+        // the rewards of the already processed blocks are not in the ledger, so they
+        // are processed again
+        print_line("Rebuilding the reward ledger from the start of the chain...");
+        summary_file.reset_rewards().await.context("couldn't reset the summary")?;
+    } else {
+        let records = read_ledger(summary_dir).await?;
+        if let Err(err) = LedgerTotals::from_records(&records).check(&summary) {
+            print_line(format!("{} {err}", "warning:".yellow()));
+        }
+    }
+
+    Ok(ledger)
+}
+
 #[instrument]
 async fn wait_on_farmer(
-    mut maybe_handles: MaybeHandles,
+    handles: Handles,
     farmer: Arc<Farmer>,
     node: Arc<Node>,
+    stop: Arc<Notify>,
+    status: Status,
 ) -> Result<()> {
+    // `stop` on the control socket shuts the farm down the same way as `ctrl_c`
+    let shutdown_requested = async {
+        tokio::select! {
+            result = signal::ctrl_c() => result.context("failed to listen ctrl-c event"),
+            _ = stop.notified() => Ok(()),
+        }
+    };
+
+    tokio::pin!(shutdown_requested);
+    // the watchdog of systemd is pinged as long as this loop runs
+    let mut heartbeat = tokio::time::interval(heartbeat_interval());
+
     // node subscription can be gracefully closed with `ctrl_c` without any problem
     // (no code needed). We need graceful closing for farmer subscriptions.
-    if let Some((plotting_handle, solution_handle)) = maybe_handles.as_mut() {
-        futures::select! {
-            _ = signal::ctrl_c().fuse() => {
-               println!(
+    let (plotting_handle, mut solution_handle) = handles;
+    loop {
+        tokio::select! {
+            _ = &mut shutdown_requested => {
+                emit(FarmEvent::Shutdown);
+                notify_stopping();
+                print_line(
                     "\nWill try to gracefully exit the application now. Please wait for a couple of seconds... If you press ctrl+c again, it will \
                     try to forcefully close the app!"
                 );
                 plotting_handle.abort();
                 solution_handle.abort();
+                break;
             }
-            res = solution_handle.fuse() => {
+            res = &mut solution_handle => {
                 return res.context("couldn't join subscription handle")?.context("solution subscription crashed");
             }
             // cannot inspect plotting sub for errors, since it may end and quit from select
+            _ = heartbeat.tick() => notify_alive(&status),
         }
-    } else {
-        // if there are not subscriptions, just wait on the kill signal
-        signal::ctrl_c().await.context("failed to listen ctrl-c event")?
     }
 
     // shutting down the farmer and the node
     let graceful_close_handle = spawn_task("graceful_shutdown_listener", async move {
         // if one of the subscriptions have not aborted yet, wait
         // Plotting might end, so we ignore result here
-        if let Some((plotting_handle, solution_handle)) = maybe_handles {
-            let _ = plotting_handle.await;
-            solution_handle.await.expect_err("Solution subscription never ends");
-        }
+        let _ = plotting_handle.await;
+        solution_handle.await.expect_err("Solution subscription never ends");
 
         Arc::try_unwrap(farmer)
             .expect("there should have been only 1 strong farmer counter")
@@ -183,30 +349,41 @@ async fn wait_on_farmer(
     });
 
     tokio::select! {
-        _ = graceful_close_handle => println!("gracefully closed the app!"),
-        _ = signal::ctrl_c() => println!("\nforcefully closing the app!"),
+        _ = graceful_close_handle => print_line("gracefully closed the app!"),
+        _ = signal::ctrl_c() => print_line("\nforcefully closing the app!"),
     }
     Ok(())
 }
 
 #[instrument]
-async fn subscribe_to_node_syncing(node: &Node) -> Result<()> {
+async fn subscribe_to_node_syncing(
+    node: &Node,
+    status: &Status,
+    is_progress_hidden: bool,
+) -> Result<()> {
     let mut syncing_progress = node
         .subscribe_syncing_progress()
         .await
         .into_eyre()
         .context("Failed to subscribe to node syncing")?
         .into_eyre()
-        .map_ok(|SyncingProgress { at, target, status: _ }| (target as _, at as _));
+        .map_ok(|SyncingProgress { at, target, status: _ }| (target, at));
 
     if let Some(syncing_result) = syncing_progress.next().await {
         let (target_block, current_block) = syncing_result.context("Sync failed")?;
-        let syncing_progress_bar = syncing_progress_bar(current_block, target_block);
+        status.set_syncing(current_block, target_block);
+        emit(FarmEvent::SyncProgress { position: current_block, target: target_block });
+        let syncing_progress_bar = syncing_progress_bar(current_block.into(), target_block.into());
+        if is_progress_hidden {
+            syncing_progress_bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
 
         while let Some(stream_result) = syncing_progress.next().await {
             let (target_block, current_block) = stream_result.context("Sync failed")?;
-            syncing_progress_bar.set_position(current_block);
-            syncing_progress_bar.set_length(target_block);
+            status.set_syncing(current_block, target_block);
+            emit(FarmEvent::SyncProgress { position: current_block, target: target_block });
+            syncing_progress_bar.set_position(current_block.into());
+            syncing_progress_bar.set_length(target_block.into());
         }
         syncing_progress_bar.finish_with_message(
             "Initial syncing is completed! Syncing will continue in the background...",
@@ -220,27 +397,47 @@ async fn subscribe_to_plotting_progress(
     farmer: Arc<Farmer>,
     is_initial_progress_finished: Arc<AtomicBool>,
     sector_size_bytes: u64,
+    progress_bars: MultiProgress,
+    status: Status,
 ) -> Result<()> {
     for (plot_id, plot) in farmer.iter_plots().await.enumerate() {
-        println!("Initial plotting for plot: #{plot_id} ({})", plot.directory().display());
+        progress_bars
+            .println(format!(
+                "Initial plotting for plot: #{plot_id} ({})",
+                plot.directory().display()
+            ))
+            .context("couldn't print to the terminal")?;
 
         let mut plotting_progress = plot.subscribe_initial_plotting_progress().await;
         let progress_bar;
+        let allocated_size = plot.allocated_space().as_u64();
+        let report_progress = |current_size| {
+            status.set_plotting(plot_id, plot.directory(), current_size, allocated_size);
+            emit(FarmEvent::PlottingProgress {
+                plot: plot_id,
+                plotted_bytes: current_size,
+                allocated_bytes: allocated_size,
+            });
+        };
 
         if let Some(plotting_result) = plotting_progress.next().await {
             let current_size = plotting_result.current_sector * sector_size_bytes;
-            progress_bar = plotting_progress_bar(current_size, plot.allocated_space().as_u64());
+            report_progress(current_size);
+            progress_bar = progress_bars
+                .add(plotting_progress_bar(current_size, plot.allocated_space().as_u64()));
 
             while let Some(stream_result) = plotting_progress.next().await {
                 let current_size = stream_result.current_sector * sector_size_bytes;
+                report_progress(current_size);
                 progress_bar.set_position(current_size);
             }
         } else {
+            status.set_plotting(plot_id, plot.directory(), allocated_size, allocated_size);
             // means initial plotting was already finished
-            progress_bar = plotting_progress_bar(
+            progress_bar = progress_bars.add(plotting_progress_bar(
                 plot.allocated_space().as_u64(),
                 plot.allocated_space().as_u64(),
-            );
+            ));
         }
         progress_bar.set_style(
             ProgressStyle::with_template(
@@ -250,6 +447,7 @@ async fn subscribe_to_plotting_progress(
             .expect("hardcoded template is correct"),
         );
         progress_bar.finish_with_message("Initial plotting finished!\n");
+        emit(FarmEvent::PlottingFinished { plot: plot_id });
     }
     is_initial_progress_finished.store(true, Ordering::Relaxed);
     summary_file
@@ -260,18 +458,39 @@ async fn subscribe_to_plotting_progress(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn subscribe_to_solutions(
     summary_file: SummaryFile,
+    ledger: Ledger,
     node: Arc<Node>,
     is_initial_progress_finished: Arc<AtomicBool>,
     reward_address: PublicKey,
     blocks_pruning: bool,
+    progress_bars: MultiProgress,
+    status: Status,
 ) -> Result<()> {
     // necessary for spacing
-    println!();
+    if !progress_bars.is_hidden() {
+        println!();
+    }
 
-    let Summary { last_processed_block_num: last_block_num, .. } =
-        summary_file.parse().await.context("parsing the summary failed")?;
+    revert_reorged_blocks(&node, &summary_file, &ledger, blocks_pruning)
+        .await
+        .context("couldn't revert the blocks of a reorg")?;
+
+    let summary = summary_file.parse().await.context("parsing the summary failed")?;
+    status.set_summary(&summary);
+    let last_block_num = summary.last_processed_block_num;
+
+    let target_block_num =
+        node.get_info().await.into_eyre().context("failed to get node info")?.finalized_block.1;
+    status.set_finalized_block(target_block_num);
+    // the events of the past blocks are not emitted as new ones
+    let first_new_block = target_block_num + 1;
+    let scanning_progress_bar = progress_bars.add(scanning_progress_bar(
+        last_block_num.into(),
+        target_block_num.max(last_block_num).into(),
+    ));
 
     // first, process the stream in a parallelized fashion,
     // after that, there will be new blocks arrived
@@ -281,37 +500,57 @@ async fn subscribe_to_solutions(
         node.clone(),
         blocks_pruning,
         summary_file.clone(),
+        ledger.clone(),
         reward_address,
         BATCH_BLOCKS,
         N_TASKS,
+        first_new_block,
+        &scanning_progress_bar,
+        &status,
     )
     .await
     .context("parallel block stream couldn't be processed")?;
+    scanning_progress_bar.finish_with_message("Rewards of the past blocks are counted!");
 
     loop {
-        let Summary { total_rewards, authored_count, vote_count, last_processed_block_num, .. } =
-            summary_file.parse().await.context("couldn't parse summary")?;
+        let Summary {
+            total_rewards,
+            authored_count,
+            vote_count,
+            last_processed_block_num,
+            token,
+            ..
+        } = summary_file.parse().await.context("couldn't parse summary")?;
+        let total_rewards = token.unwrap_or_default().format(total_rewards.0, Units::Ssc);
 
         if is_initial_progress_finished.load(Ordering::Relaxed) {
-            // use carriage return to overwrite the current value
-            // instead of inserting a new line
-            print!(
-                "\rYou have earned: {total_rewards} SSC(s), farmed {authored_count} block(s), and \
-                 voted on {vote_count} block(s)! This data is derived from the first \
-                 {last_processed_block_num} blocks.\n",
-            );
-            // flush the stdout to make sure values are printed
-            std::io::stdout().flush().expect("Failed to flush stdout");
+            // the rewards are emitted one by one with JSON, and not mixed with the logs of
+            // `--verbose`
+            if !progress_bars.is_hidden() {
+                // use carriage return to overwrite the current value
+                // instead of inserting a new line
+                print!(
+                    "\rYou have earned: {total_rewards}, farmed {authored_count} block(s), and \
+                     voted on {vote_count} block(s)! This data is derived from the first \
+                     {last_processed_block_num} blocks.\n",
+                );
+                // flush the stdout to make sure values are printed
+                std::io::stdout().flush().expect("Failed to flush stdout");
+            }
 
             // now, process the blocks without paralellization
             process_block_stream(
-                last_block_num,
+                last_processed_block_num,
                 node.clone(),
                 blocks_pruning,
                 summary_file.clone(),
+                ledger.clone(),
                 reward_address,
                 1,
                 1,
+                first_new_block,
+                &ProgressBar::hidden(),
+                &status,
             )
             .await
             .context("sequential block stream couldn't be processed")?;
@@ -319,6 +558,88 @@ async fn subscribe_to_solutions(
     }
 }
 
+/// checks whether the chain still has the last processed block, and if not
+/// (i.e. after a reorg), reverts the summary and the ledger to the last
+/// processed block which the chain still has, so that the reverted blocks are
+/// processed again
+async fn revert_reorged_blocks(
+    node: &Node,
+    summary_file: &SummaryFile,
+    ledger: &Ledger,
+    blocks_pruning: bool,
+) -> Result<()> {
+    let summary = summary_file.parse().await.context("parsing the summary failed")?;
+    let Some(last_processed_block) = &summary.last_processed_block else {
+        return Ok(());
+    };
+
+    // pruned blocks are deep enough to never be reverted
+    let is_in_chain = |block: &ProcessedBlock| match node.block_hash(block.number).into_eyre() {
+        Ok(Some(hash)) => Ok(format!("{hash:?}") == block.hash),
+        Ok(None) if blocks_pruning => Ok(true),
+        Ok(None) => Ok(false),
+        Err(err) => Err(err.wrap_err("couldn't get block hash from node")),
+    };
+    if is_in_chain(last_processed_block)? {
+        return Ok(());
+    }
+
+    let fork_point = find_fork_point(&summary, &ledger.records().await?, is_in_chain)?;
+    let reverted = ledger
+        .revert_after(fork_point.as_ref().map(|block| block.number))
+        .await
+        .context("couldn't revert the ledger")?;
+    let since = match &fork_point {
+        Some(block) => format!("after the block #{}", block.number),
+        None => "from the start of the chain".to_owned(),
+    };
+    print_line(format!(
+        "The chain no longer has the block #{} which was processed last, reverting {} reward(s) \
+         and vote(s) {since}...",
+        last_processed_block.number,
+        reverted.len(),
+    ));
+    summary_file
+        .revert(LedgerTotals::from_records(&reverted), fork_point)
+        .await
+        .context("couldn't revert the summary")?;
+
+    Ok(())
+}
+
+/// returns the last block before the last processed one which the chain still
+/// has, `None` if there is none
+///
+/// the recent blocks of the summary are checked first, then the blocks of the
+/// ledger, which only knows the hashes of the blocks with rewards
+pub(crate) fn find_fork_point(
+    summary: &Summary,
+    records: &[LedgerRecord],
+    mut is_in_chain: impl FnMut(&ProcessedBlock) -> Result<bool>,
+) -> Result<Option<ProcessedBlock>> {
+    let Some(last_processed_block) = &summary.last_processed_block else {
+        return Ok(None);
+    };
+    let recent_blocks = summary.recent_blocks.iter().rev().cloned();
+    let ledger_blocks = records.iter().rev().map(|record| ProcessedBlock {
+        number: record.block_number,
+        hash: record.block_hash.clone(),
+    });
+
+    let mut checked_below = last_processed_block.number;
+    for block in recent_blocks.chain(ledger_blocks) {
+        // the blocks of the ledger overlap with the recent ones
+        if block.number >= checked_below {
+            continue;
+        }
+        if is_in_chain(&block)? {
+            return Ok(Some(block));
+        }
+        checked_below = block.number;
+    }
+    Ok(None)
+}
+
 /// nice looking progress bar for the initial plotting :)
 fn plotting_progress_bar(current_size: u64, total_size: u64) -> ProgressBar {
     let pb = ProgressBar::new(total_size);
@@ -340,6 +661,22 @@ fn plotting_progress_bar(current_size: u64, total_size: u64) -> ProgressBar {
     pb
 }
 
+/// nice looking progress bar for counting the rewards of the past blocks :)
+fn scanning_progress_bar(current_block: u64, total_blocks: u64) -> ProgressBar {
+    let pb = ProgressBar::new(total_blocks);
+    pb.set_style(
+        ProgressStyle::with_template(
+            " [{elapsed_precise}] {percent}% [{wide_bar:.green}] ({pos}/{len}) {per_sec}, {msg}, \
+             ETA: {eta_precise} ",
+        )
+        .expect("hardcoded template is correct")
+        .progress_chars("█▉▊▋▌▍▎▏  "),
+    );
+    pb.set_message("counting rewards");
+    pb.set_position(current_block);
+    pb
+}
+
 /// nice looking progress bar for the syncing :)
 fn syncing_progress_bar(current_block: u64, total_blocks: u64) -> ProgressBar {
     let pb = ProgressBar::new(total_blocks);
@@ -366,10 +703,14 @@ fn syncing_progress_bar(current_block: u64, total_blocks: u64) -> ProgressBar {
 fn not_yet_processed_block_nums_stream(
     node: std::sync::Arc<Node>,
     mut last_block_num: subspace_sdk::node::BlockNumber,
+    status: Status,
 ) -> impl Stream<Item = Result<subspace_sdk::node::BlockNumber>> {
     async_stream::try_stream! {
         loop {
-            let last_retrieved_num = node.get_info().await.into_eyre().context("failed to receive Info from node")?.finalized_block.1;
+            let node_info = node.get_info().await.into_eyre().context("failed to receive Info from node")?;
+            let last_retrieved_num = node_info.finalized_block.1;
+            status.set_connected_peers(node_info.connected_peers);
+            status.set_finalized_block(last_retrieved_num);
 
             if last_block_num == last_retrieved_num {
                 break;
@@ -383,25 +724,30 @@ fn not_yet_processed_block_nums_stream(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn process_block_stream(
     last_block_num: subspace_sdk::node::BlockNumber,
     node: Arc<Node>,
     blocks_pruning: bool,
     summary_file: SummaryFile,
+    ledger: Ledger,
     reward_address: PublicKey,
     batch_blocks: usize,
     n_tasks: usize,
+    first_new_block: BlockNumber,
+    progress_bar: &ProgressBar,
+    status: &Status,
 ) -> Result<()> {
-    let stream = not_yet_processed_block_nums_stream(node.clone(), last_block_num);
+    let stream = not_yet_processed_block_nums_stream(node.clone(), last_block_num, status.clone());
 
     futures::pin_mut!(stream);
 
-    let mut current_iter = 0;
-
     stream
-        .try_filter_map(|block| match node.block_hash(block).into_eyre() {
-            Ok(Some(block_hash)) => future::ok(Some(block_hash)),
-            Ok(None) if blocks_pruning => future::ok(None),
+        // pruned blocks are kept in the batches without a hash, so that the summary
+        // knows they are processed
+        .and_then(|block| match node.block_hash(block).into_eyre() {
+            Ok(Some(block_hash)) => future::ok((block, Some(block_hash))),
+            Ok(None) if blocks_pruning => future::ok((block, None)),
             Ok(None) =>
                 future::err(eyre!("node database is probably corrupted, try wiping the node")),
             Err(err) => future::err(err.wrap_err("couldn't get block hash from node")),
@@ -413,10 +759,26 @@ async fn process_block_stream(
         .try_for_each(|blocks| {
             let node_clone = node.clone();
             let summary_clone = summary_file.clone();
+            let ledger_clone = ledger.clone();
+            // every block of the batch is processed once the summary is updated, so the
+            // next run continues from the block after the batch
+            let last_processed_block_num = blocks.last().map(|(number, _)| number + 1);
+            // the blocks of the batch are persisted, so that reorgs can be detected and
+            // reverted
+            let processed_blocks = blocks
+                .iter()
+                .filter_map(|(number, hash)| {
+                    hash.map(|hash| ProcessedBlock { number: *number, hash: format!("{hash:?}") })
+                })
+                .collect::<Vec<_>>();
+            let last_processed_block = processed_blocks.last().cloned();
+            let blocks = blocks
+                .into_iter()
+                .filter_map(|(number, hash)| hash.map(|hash| (number, hash)))
+                .collect();
             async move {
-                current_iter += 1;
                 // We iterate over hashes
-                let (rewards, votes, author) = get_rewards_votes_author_info_from_blocks(
+                let (records, authored_blocks) = get_rewards_votes_author_info_from_blocks(
                     node_clone,
                     blocks,
                     reward_address,
@@ -426,16 +788,42 @@ async fn process_block_stream(
                 .await
                 .context("couldn't get rewards, votes and author info")?;
 
-                summary_clone
+                // the ledger is appended first, so that the summary never counts rewards
+                // which are not in the ledger
+                let LedgerTotals { vote_count, total_rewards, .. } =
+                    LedgerTotals::from_records(&records);
+                ledger_clone
+                    .append(records.clone())
+                    .await
+                    .context("couldn't append to the ledger")?;
+
+                let summary = summary_clone
                     .update(SummaryUpdateFields {
-                        new_authored_count: author,
-                        new_vote_count: votes,
-                        new_reward: Rewards(rewards),
-                        new_parsed_blocks: current_iter * batch_blocks as u32,
+                        new_authored_count: authored_blocks.len() as u64,
+                        new_vote_count: vote_count,
+                        new_reward: Rewards(total_rewards),
+                        last_processed_block_num,
+                        last_processed_block,
+                        processed_blocks,
                         ..Default::default()
                     })
                     .await
                     .context("couldn't update the summary")?;
+                status.set_summary(&summary);
+                emit_block_events(
+                    &authored_blocks,
+                    &records,
+                    first_new_block,
+                    &summary.token.unwrap_or_default(),
+                );
+
+                if let Some(last_processed_block_num) = last_processed_block_num {
+                    let position = u64::from(last_processed_block_num);
+                    // the chain grows while the past blocks are processed
+                    progress_bar
+                        .set_length(progress_bar.length().unwrap_or_default().max(position));
+                    progress_bar.set_position(position);
+                }
 
                 Ok(())
             }
@@ -443,66 +831,121 @@ async fn process_block_stream(
         .await
 }
 
+/// emits the events of the given blocks authored by the farmer, and of the
+/// given records of the ledger, in the order of the blocks
+///
+/// the blocks before `first_new_block` were finalized before the farm started,
+/// so they are only counted in the summary and the ledger
+fn emit_block_events(
+    authored_blocks: &[BlockNumber],
+    records: &[LedgerRecord],
+    first_new_block: BlockNumber,
+    token: &Token,
+) {
+    if output_format() != OutputFormat::Json {
+        return;
+    }
+    let mut events = authored_blocks
+        .iter()
+        .map(|block_number| {
+            (*block_number, FarmEvent::AuthoredBlock { block_number: *block_number })
+        })
+        .chain(
+            records
+                .iter()
+                .map(|record| (record.block_number, FarmEvent::from_record(record, token))),
+        )
+        .filter(|(block_number, _)| *block_number >= first_new_block)
+        .collect::<Vec<_>>();
+    // blocks are processed in parallel, so they are not in order
+    events.sort_by_key(|(block_number, _)| *block_number);
+    for (_, event) in events {
+        emit(event);
+    }
+}
+
+/// returns the ledger records of the farmer in the given blocks, and the
+/// blocks authored by the farmer
 async fn get_rewards_votes_author_info_from_blocks(
     node: Arc<Node>,
-    blocks: Vec<Hash>,
+    blocks: Vec<(BlockNumber, Hash)>,
     reward_address: PublicKey,
     n_tasks: usize,
     blocks_pruning: bool,
-) -> Result<(u128, u64, u64)> {
-    let (rewards, votes, author) = futures::stream::iter(blocks)
+) -> Result<(Vec<LedgerRecord>, Vec<BlockNumber>)> {
+    let (records, authored_blocks) = futures::stream::iter(blocks)
         // We scan each hash and find 3 things:
-        // - Total amount of rewards
-        // - Number of votes
-        // - Number of times we authored a block
-        .map(|hash| {
-            let is_author = match node
+        // - Rewards of the farmer
+        // - Votes of the farmer
+        // - Whether the farmer authored the block
+        .map(|(block_number, hash)| {
+            let pre_digest = match node
                 .block_header(hash)
                 .into_eyre()
                 .context("failed to retrieve block header from node")?
             {
-                Some(block_header) => Ok(block_header
-                    .pre_digest
-                    .map(|pre_digest| pre_digest.solution.reward_address == reward_address)
-                    .unwrap_or_default()),
-                None if blocks_pruning => Ok(false),
+                Some(block_header) => Ok(block_header.pre_digest),
+                None if blocks_pruning => Ok(None),
                 None => Err(eyre!("node database is probably corrupted, try wiping the node")),
             }
             .context("couldn't get the author info from block header")?;
+            let is_author = pre_digest
+                .as_ref()
+                .is_some_and(|pre_digest| pre_digest.solution.reward_address == reward_address);
+            // slots are one second long, so the slot of a block is its unix timestamp
+            let timestamp = pre_digest.map(|pre_digest| u64::from(pre_digest.slot));
 
-            let rewards_future = node
+            let records_future = node
                 .get_events(Some(hash))
                 .into_eyre()
-                .map_ok(|events| {
+                .map_ok(move |events| {
                     events
                         .into_iter()
-                        .map(|event| match event {
-                            Event::Rewards(
-                                RewardsEvent::VoteReward { voter: author, reward }
-                                | RewardsEvent::BlockReward { block_author: author, reward },
-                            ) if author == reward_address.into() => (reward, 0),
-                            Event::Subspace(SubspaceEvent::FarmerVote {
-                                reward_address: author,
-                                ..
-                            }) if author == reward_address.into() => (0, 1),
-                            _ => (0, 0),
-                        })
-                        .fold((0, 0), |(rewards, votes), (new_rewards, new_votes)| {
-                            (rewards + new_rewards, votes + new_votes)
+                        .filter_map(|event| {
+                            let (kind, amount) = match event {
+                                Event::Rewards(RewardsEvent::BlockReward {
+                                    block_author: author,
+                                    reward,
+                                }) if author == reward_address.into() =>
+                                    (RewardKind::BlockReward, reward),
+                                Event::Rewards(RewardsEvent::VoteReward {
+                                    voter: author,
+                                    reward,
+                                }) if author == reward_address.into() =>
+                                    (RewardKind::VoteReward, reward),
+                                Event::Subspace(SubspaceEvent::FarmerVote {
+                                    reward_address: author,
+                                    ..
+                                }) if author == reward_address.into() => (RewardKind::Vote, 0),
+                                _ => return None,
+                            };
+                            Some(LedgerRecord {
+                                block_number,
+                                block_hash: format!("{hash:?}"),
+                                timestamp,
+                                kind,
+                                amount,
+                            })
                         })
+                        .collect::<Vec<_>>()
                 })
-                .map_ok(move |(rewards, votes)| (rewards, votes, if is_author { 1 } else { 0 }));
+                .map_ok(move |records| (records, is_author.then_some(block_number)));
 
-            Result::Ok(rewards_future)
+            Result::Ok(records_future)
         })
         // We calculate each block in parallel
         .try_buffer_unordered(n_tasks)
-        // After that we sum up result
-        .try_fold((0, 0, 0), |(rewards, votes, author), (new_rewards, new_votes, new_author)| {
-            futures::future::ok((rewards + new_rewards, votes + new_votes, author + new_author))
-        })
+        // After that we collect the records and the authored blocks
+        .try_fold(
+            (vec![], vec![]),
+            |(mut records, mut authored_blocks), (new_records, authored)| {
+                records.extend(new_records);
+                authored_blocks.extend(authored);
+                futures::future::ok((records, authored_blocks))
+            },
+        )
         .await
         .context("error in stream encountered in try_fold step")?;
 
-    Ok((rewards, votes, author))
+    Ok((records, authored_blocks))
 }
@@ -1,22 +1,75 @@
+use std::path::Path;
+
+use chrono::NaiveDate;
+use clap::Args;
 use color_eyre::eyre::{Context, Result};
+use owo_colors::OwoColorize;
 use single_instance::SingleInstance;
+use subspace_sdk::ByteSize;
+
+use crate::config::{parse_config, PlotConfig};
+use crate::control::{send_request, ControlRequest, ControlResponse};
+use crate::profile::{profile, DEFAULT_PROFILE};
+use crate::status::{NodeStatus, PlotStatus, StatusReport};
+use crate::summary::history::{date_timestamp, history, sparkline, today, Period};
+use crate::summary::ledger::{query_ledger, read_ledger, LedgerTotals};
+use crate::summary::storage::RecordQuery;
+use crate::summary::{
+    active_summary_dir, summary_dir, summary_dirs, Summary, SummaryFile, Token, Units,
+};
+use crate::utils::{control_socket_getter, date_parser};
 
-use crate::commands::farm::SINGLE_INSTANCE;
-use crate::summary::{Summary, SummaryFile};
+/// arguments of the `info` command
+#[derive(Debug, Default, Args)]
+pub(crate) struct InfoArgs {
+    /// also shows the rewards, votes and authored blocks per period of time
+    #[arg(long)]
+    history: bool,
+    /// length of the periods of the history (in UTC)
+    #[arg(long, value_enum, default_value_t, requires = "history")]
+    by: Period,
+    /// only shows the history from the given date (`YYYY-MM-DD`)
+    #[arg(long, value_parser = date_parser, requires = "history")]
+    since: Option<NaiveDate>,
+    /// units the rewards are shown in
+    #[arg(long, value_enum, default_value_t)]
+    units: Units,
+}
 
-/// implementation of the `init` command.
+/// implementation of the `info` command.
 ///
 /// informs the user about the current farming instance
-pub(crate) async fn info() -> Result<()> {
-    let instance =
-        SingleInstance::new(SINGLE_INSTANCE).context("failed to initialize single instance")?;
+pub(crate) async fn info(InfoArgs { history, by, since, units }: InfoArgs) -> Result<()> {
+    let profile = profile();
+    if profile.name() != DEFAULT_PROFILE {
+        println!("Profile: {}", profile.name());
+    }
+
+    let instance = SingleInstance::new(&profile.instance_lock())
+        .context("failed to initialize single instance")?;
     if !instance.is_single() {
         println!("A farmer instance is active!");
+        // the farm may be starting, or not have a control socket (i.e. not on Unix)
+        match send_request(&control_socket_getter(), &ControlRequest::Status).await {
+            Ok(Some(ControlResponse::Status(report))) => print_live_status(&report),
+            Ok(_) => (),
+            Err(err) => println!("{} couldn't get the live status: {err:#}", "warning:".yellow()),
+        }
     } else {
         println!("There is no active farmer instance...");
     }
 
-    let summary_file = SummaryFile::new(None).await?;
+    // the config may be gone (i.e. wiped), in which case the default locations are
+    // used
+    let config = parse_config().ok();
+    let summaries_dir =
+        config.as_ref().map_or_else(summary_dir, |config| config.farmer.summary_directory.clone());
+    let summary_dir = active_summary_dir(&summaries_dir);
+    let summary_file = SummaryFile::new(&summary_dir, None).await?;
+    let summary = summary_file
+        .parse()
+        .await
+        .context("couldn't parse summary file, are you sure you have ran `farm` command?")?;
     let Summary {
         user_space_pledged,
         authored_count,
@@ -24,18 +77,31 @@ pub(crate) async fn info() -> Result<()> {
         total_rewards,
         initial_plotting_finished,
         last_processed_block_num: last_block_parsed,
-    } = summary_file
-        .parse()
-        .await
-        .context("couldn't parse summary file, are you sure you have ran `farm` command?")?;
+        key,
+        token,
+        ..
+    } = summary.clone();
+    let token = token.unwrap_or_default();
+
+    if let Some(key) = &key {
+        println!("Summary of {key}");
+    }
 
     println!("You have pledged to the network: {user_space_pledged}");
 
+    // the summary does not know about the individual plots, so list them from the
+    // config, if it is still there
+    if let Some(config) = &config {
+        for (plot_id, PlotConfig { directory, size }) in config.farmer.plots.iter().enumerate() {
+            println!("  plot #{plot_id}: {size} at `{}`", directory.display());
+        }
+    }
+
     println!("Farmed {authored_count} block(s)");
 
     println!("Voted on {vote_count} block(s)");
 
-    println!("{total_rewards} SSC(s) earned!",);
+    println!("{} earned!", token.format(total_rewards.0, units));
 
     println!("This data is derived from the first {last_block_parsed} blocks in the chain!",);
 
@@ -45,5 +111,126 @@ pub(crate) async fn info() -> Result<()> {
         println!("Initial plotting is not finished...");
     }
 
+    // while farming, the ledger is ahead of the summary until a batch of blocks is
+    // processed, so they are only compared when there is no active farmer
+    if instance.is_single() {
+        if let Ok(records) = read_ledger(&summary_dir).await {
+            if let Err(err) = LedgerTotals::from_records(&records).check(&summary) {
+                println!("{} {err}", "warning:".yellow());
+            }
+        }
+    }
+
+    print_other_summaries(&summaries_dir, &summary_dir, units).await?;
+
+    if history {
+        print_history(&summary_dir, by, since, &token, units).await?;
+    }
+
+    Ok(())
+}
+
+/// prints the live status of the running farm
+fn print_live_status(StatusReport { uptime_secs, node, plots, .. }: &StatusReport) {
+    let NodeStatus { is_synced, connected_peers, sync_position, sync_target, finalized_block } =
+        node;
+    let sync = match (is_synced, sync_position, sync_target) {
+        (true, ..) => "synced".to_owned(),
+        (false, Some(position), Some(target)) => format!("syncing (#{position} of #{target})"),
+        (false, ..) => "syncing".to_owned(),
+    };
+    let peers = connected_peers.map_or_else(String::new, |peers| format!(", {peers} peer(s)"));
+    let finalized =
+        finalized_block.map_or_else(String::new, |block| format!(", finalized block #{block}"));
+    println!("  node: {sync}{peers}{finalized}");
+
+    for PlotStatus { id, plotted_bytes, allocated_bytes, .. } in plots {
+        let percent = match allocated_bytes {
+            0 => 100.0,
+            allocated => *plotted_bytes as f64 / *allocated as f64 * 100.0,
+        };
+        println!(
+            "  plot #{id}: {percent:.1}% plotted ({} of {})",
+            ByteSize::b(*plotted_bytes),
+            ByteSize::b(*allocated_bytes)
+        );
+    }
+
+    let (hours, minutes) = (uptime_secs / 3600, uptime_secs % 3600 / 60);
+    println!("  farming for {hours}h {minutes}m");
+}
+
+/// prints the totals of the summaries other than the active one, i.e. of the
+/// other chains and reward addresses farmed before
+async fn print_other_summaries(
+    summaries_dir: &Path,
+    active_summary_dir: &Path,
+    units: Units,
+) -> Result<()> {
+    let mut other_summaries = vec![];
+    for summary_dir in summary_dirs(summaries_dir)? {
+        if summary_dir != active_summary_dir {
+            let summary = SummaryFile::new(&summary_dir, None).await?.parse().await?;
+            other_summaries.push(summary);
+        }
+    }
+    if other_summaries.is_empty() {
+        return Ok(());
+    }
+
+    println!();
+    println!("Other summaries:");
+    for Summary { key, authored_count, vote_count, total_rewards, token, .. } in other_summaries {
+        let key = key.map_or_else(|| "unknown chain".to_owned(), |key| key.to_string());
+        // every chain has its own token
+        let total_rewards = token.unwrap_or_default().format(total_rewards.0, units);
+        println!(
+            "  {key}: {total_rewards} earned, farmed {authored_count} block(s), and voted on \
+             {vote_count} block(s)"
+        );
+    }
+
+    Ok(())
+}
+
+/// prints the rewards, votes and authored blocks per period, along with a
+/// sparkline of the rewards
+async fn print_history(
+    summary_dir: &Path,
+    by: Period,
+    since: Option<NaiveDate>,
+    token: &Token,
+    units: Units,
+) -> Result<()> {
+    let query = RecordQuery { from_timestamp: since.map(date_timestamp), ..Default::default() };
+    let records = query_ledger(summary_dir, query)
+        .await
+        .context("couldn't read the reward ledger, are you sure you have ran `farm` command?")?;
+    let periods = history(&records, by, since, today());
+
+    println!();
+    if periods.is_empty() {
+        println!("There are no rewards in the history yet...");
+        return Ok(());
+    }
+
+    let rewards_header = format!("Rewards ({})", token.unit(units));
+    println!("{:<12} {rewards_header:>32} {:>8} {:>8}", "Period", "Votes", "Blocks");
+    for (start, totals) in &periods {
+        let LedgerTotals { authored_count, vote_count, total_rewards } = totals;
+        println!(
+            "{:<12} {:>32} {vote_count:>8} {authored_count:>8}",
+            by.label(*start),
+            token.amount(*total_rewards, units)
+        );
+    }
+    let rewards = periods.iter().map(|(_, totals)| totals.total_rewards).collect::<Vec<_>>();
+    println!("Rewards: {}", sparkline(&rewards));
+
+    let undated = records.iter().filter(|record| record.timestamp.is_none()).count();
+    if undated > 0 {
+        println!("{undated} record(s) of pruned blocks have no timestamp, and are not shown");
+    }
+
     Ok(())
 }
@@ -1,30 +1,101 @@
+use std::collections::HashSet;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use color_eyre::eyre::{Context, Result};
-use strum::IntoEnumIterator;
+use clap::Args;
+use color_eyre::eyre::{eyre, Context, Result};
+use color_eyre::Help;
+use subspace_sdk::{ByteSize, PublicKey};
 
 use crate::config::{
     create_config, AdvancedFarmerSettings, AdvancedNodeSettings, ChainConfig, Config, FarmerConfig,
-    NodeConfig, DEFAULT_PLOT_SIZE,
+    NodeConfig, PlotConfig, CONFIG_VERSION, DEFAULT_PLOT_SIZE,
 };
+use crate::home::profile_home;
+use crate::summary::summary_dir as summary_dir_getter;
 use crate::utils::{
-    directory_parser, get_user_input, node_directory_getter, node_name_parser,
-    plot_directory_getter, print_ascii_art, print_run_executable_command, print_version,
-    reward_address_parser, size_parser,
+    cache_directory_getter, cache_size_parser, directory_parser, existing_directory_parser,
+    get_user_input, node_directory_getter, node_name_parser, plot_directory_getter,
+    print_ascii_art, print_run_executable_command, print_version, reward_address_parser,
+    size_parser, yes_or_no_parser,
 };
 
+/// arguments of the `init` command
+///
+/// every value which is not supplied here is prompted for, unless `yes` is
+/// set, in which case the defaults are used and nothing is prompted
+#[derive(Debug, Default, Args)]
+pub(crate) struct InitArgs {
+    /// farmer/reward address
+    #[arg(long, env = "SUBSPACE_CLI_REWARD_ADDRESS", value_parser = reward_address_parser)]
+    reward_address: Option<PublicKey>,
+    /// path for storing plot files, can be repeated (or comma separated) for
+    /// multiple plots
+    #[arg(long = "plot-dir", env = "SUBSPACE_CLI_PLOT_DIR", value_delimiter = ',')]
+    plot_dirs: Vec<PathBuf>,
+    /// size of the plot, either one for all the plots or one per `--plot-dir`
+    #[arg(
+        long = "plot-size",
+        env = "SUBSPACE_CLI_PLOT_SIZE",
+        value_delimiter = ',',
+        value_parser = size_parser
+    )]
+    plot_sizes: Vec<ByteSize>,
+    /// path for storing node files
+    #[arg(long, env = "SUBSPACE_CLI_NODE_DIR")]
+    node_dir: Option<PathBuf>,
+    /// chain to farm, either the name of a known chain or the path of a chain
+    /// spec file
+    #[arg(long, env = "SUBSPACE_CLI_CHAIN", value_parser = ChainConfig::from_str)]
+    chain: Option<ChainConfig>,
+    /// node name to be identified on the network
+    #[arg(long, env = "SUBSPACE_CLI_NAME", value_parser = node_name_parser)]
+    name: Option<String>,
+    /// path for storing the piece cache
+    #[arg(long, env = "SUBSPACE_CLI_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// size of the piece cache
+    #[arg(long, env = "SUBSPACE_CLI_CACHE_SIZE", value_parser = cache_size_parser)]
+    cache_size: Option<ByteSize>,
+    /// path for storing the DSN provider storage (defaults to a directory in
+    /// the node directory)
+    #[arg(long, env = "SUBSPACE_CLI_PROVIDER_STORAGE_DIR")]
+    provider_storage_dir: Option<PathBuf>,
+    /// path for storing the summary of the farming
+    #[arg(long, env = "SUBSPACE_CLI_SUMMARY_DIR")]
+    summary_dir: Option<PathBuf>,
+    /// also prompt for the advanced settings (cache, provider storage and
+    /// summary)
+    #[arg(long)]
+    advanced: bool,
+    /// create the given directories if they do not exist
+    #[arg(long, env = "SUBSPACE_CLI_CREATE_DIRS")]
+    create_dirs: bool,
+    /// do not prompt, use the defaults for the values which are not supplied
+    #[arg(short, long, env = "SUBSPACE_CLI_YES")]
+    yes: bool,
+}
+
 /// implementation of the `init` command
 ///
 /// prints a very cool ascii art,
-/// creates a config file from the user inputs
-pub(crate) fn init() -> Result<()> {
-    let (mut config_file, config_path) = create_config()?;
+/// creates a config file from the supplied arguments and the user inputs
+pub(crate) fn init(args: InitArgs) -> Result<()> {
     print_ascii_art();
     print_version();
     println!();
     println!("Configuration creation process has started...");
-    let config = get_config_from_user_inputs()?;
+    let mut config = get_config_from_user_inputs(args)?;
+    // the paths inside the data directory are kept relative, so that the data
+    // directory can be moved to another location or machine
+    if let Some(profile_home) = profile_home() {
+        config.relativize_paths(&profile_home);
+    }
+
+    // the config file is truncated on creation, so only create it once we have a
+    // valid config
+    let (mut config_file, config_path) = create_config()?;
     config_file
         .write_all(toml::to_string_pretty(&config).wrap_err("Failed to write config")?.as_ref())
         .wrap_err("Failed to write config")?;
@@ -37,79 +108,263 @@ pub(crate) fn init() -> Result<()> {
     Ok(())
 }
 
-/// gets the necessary information from user, and writes them to the given
-/// configuration file
-fn get_config_from_user_inputs() -> Result<Config> {
+/// gets the necessary information from the arguments and the user, and
+/// returns the [`Config`] to be written to the configuration file
+fn get_config_from_user_inputs(args: InitArgs) -> Result<Config> {
+    let InitArgs {
+        reward_address,
+        plot_dirs,
+        plot_sizes,
+        node_dir,
+        chain,
+        name,
+        cache_dir,
+        cache_size,
+        provider_storage_dir,
+        summary_dir,
+        advanced,
+        create_dirs,
+        yes: non_interactive,
+    } = args;
+
     // GET USER INPUTS...
     // get reward address
-    let reward_address =
-        get_user_input("Enter your farmer/reward address: ", None, reward_address_parser)?;
+    let reward_address = match reward_address {
+        Some(reward_address) => reward_address,
+        None if non_interactive => return Err(missing_argument("--reward-address")),
+        None => get_user_input("Enter your farmer/reward address: ", None, reward_address_parser)?,
+    };
 
     // get node name
     let default_node_name = whoami::username();
-    let node_name = get_user_input(
-        &format!(
-            "Enter your node name to be identified on the network(defaults to \
-             `{default_node_name}`, press enter to use the default): "
-        ),
-        (default_node_name != "root").then_some(default_node_name),
-        node_name_parser,
-    )?;
-
-    // get plot directory
-    let default_plot_loc = plot_directory_getter();
-    let plot_directory = get_user_input(
-        &format!(
-            "Specify a path for storing plot files (press enter to use the default: \
-             `{default_plot_loc:?}`): ",
-        ),
-        Some(default_plot_loc),
-        directory_parser,
-    )?;
+    let node_name = match name {
+        Some(name) => name,
+        None if non_interactive => node_name_parser(&default_node_name)
+            .wrap_err("couldn't use the username as the node name, please supply `--name`")?,
+        None => get_user_input(
+            &format!(
+                "Enter your node name to be identified on the network(defaults to \
+                 `{default_node_name}`, press enter to use the default): "
+            ),
+            (default_node_name != "root").then_some(default_node_name),
+            node_name_parser,
+        )?,
+    };
 
     let default_node_loc = node_directory_getter();
-    let node_directory = get_user_input(
-        &format!(
-            "Specify a path for storing node files (press enter to use the default: \
-             `{default_node_loc:?}`): ",
-        ),
-        Some(default_node_loc),
-        directory_parser,
-    )?;
-
-    // get plot size
-    let plot_size = get_user_input(
-        &format!(
-            "Specify a plot size (defaults to `{DEFAULT_PLOT_SIZE}`, press enter to use the \
-             default): "
-        ),
-        Some(DEFAULT_PLOT_SIZE),
-        size_parser,
-    )?;
+    let node_directory = match node_dir {
+        Some(node_dir) => init_directory(&node_dir, create_dirs)?,
+        None if non_interactive => init_directory(&default_node_loc, create_dirs)?,
+        None => get_user_input(
+            &format!(
+                "Specify a path for storing node files (press enter to use the default: \
+                 `{default_node_loc:?}`): ",
+            ),
+            Some(default_node_loc),
+            directory_parser,
+        )?,
+    };
+
+    // get plots
+    let plots = if plot_dirs.is_empty() && plot_sizes.is_empty() && !non_interactive {
+        get_plots_from_user_inputs()?
+    } else {
+        get_plots_from_args(plot_dirs, plot_sizes, create_dirs)?
+    };
 
     // get chain
     let default_chain = ChainConfig::Gemini3d;
-    let chain = get_user_input(
-        &format!(
-            "Specify the chain to farm. Available options are: {:?}. \n Defaults to \
-             `{default_chain:?}`, press enter to use the default:",
-            ChainConfig::iter().collect::<Vec<_>>()
-        ),
-        Some(default_chain),
-        ChainConfig::from_str,
-    )?;
+    let chain = match chain {
+        Some(chain) => chain,
+        None if non_interactive => default_chain,
+        None => get_user_input(
+            &format!(
+                "Specify the chain to farm. Available options are: {:?}, or the path of a chain \
+                 spec file. \n Defaults to `{default_chain:?}`, press enter to use the default:",
+                ChainConfig::known().collect::<Vec<_>>()
+            ),
+            Some(default_chain),
+            ChainConfig::from_str,
+        )?,
+    };
+
+    // get advanced settings, they are only prompted for with `--advanced`
+    let prompt_advanced = advanced && !non_interactive;
+    if prompt_advanced {
+        println!("Advanced settings:");
+    }
+
+    let default_cache_loc = cache_directory_getter();
+    let cache_directory = match cache_dir {
+        Some(cache_dir) => init_directory(&cache_dir, create_dirs)?,
+        None if prompt_advanced => get_user_input(
+            &format!(
+                "Specify a path for storing the piece cache (press enter to use the default: \
+                 `{default_cache_loc:?}`): ",
+            ),
+            Some(default_cache_loc),
+            directory_parser,
+        )?,
+        None => default_cache_loc,
+    };
+
+    let default_cache_size = AdvancedFarmerSettings::default().cache_size;
+    let cache_size = match cache_size {
+        Some(cache_size) => cache_size,
+        None if prompt_advanced => get_user_input(
+            &format!(
+                "Specify a size for the piece cache (defaults to `{default_cache_size}`, press \
+                 enter to use the default): "
+            ),
+            Some(default_cache_size),
+            cache_size_parser,
+        )?,
+        None => default_cache_size,
+    };
+
+    let default_provider_storage_loc = node_directory.join("provider-storage");
+    let provider_storage_directory = match provider_storage_dir {
+        Some(provider_storage_dir) => init_directory(&provider_storage_dir, create_dirs)?,
+        None if prompt_advanced => get_user_input(
+            &format!(
+                "Specify a path for storing the provider storage of the node (press enter to use \
+                 the default: `{default_provider_storage_loc:?}`): ",
+            ),
+            Some(default_provider_storage_loc),
+            directory_parser,
+        )?,
+        None => default_provider_storage_loc,
+    };
+
+    let default_summary_loc = summary_dir_getter();
+    let summary_directory = match summary_dir {
+        Some(summary_dir) => init_directory(&summary_dir, create_dirs)?,
+        None if prompt_advanced => get_user_input(
+            &format!(
+                "Specify a path for storing the summary of the farming (press enter to use the \
+                 default: `{default_summary_loc:?}`): ",
+            ),
+            Some(default_summary_loc),
+            directory_parser,
+        )?,
+        None => default_summary_loc,
+    };
 
     let farmer_config = FarmerConfig {
-        plot_size,
-        plot_directory,
+        plots,
         reward_address,
-        advanced: AdvancedFarmerSettings::default(),
+        cache_directory,
+        summary_directory,
+        summary_storage: Default::default(),
+        advanced: AdvancedFarmerSettings { cache_size, ..Default::default() },
     };
     let node_config = NodeConfig {
         name: node_name,
         directory: node_directory,
+        provider_storage_directory,
         advanced: AdvancedNodeSettings::default(),
     };
 
-    Ok(Config { farmer: farmer_config, node: node_config, chain })
+    Ok(Config {
+        version: CONFIG_VERSION,
+        farmer: farmer_config,
+        node: node_config,
+        chain,
+        metrics: None,
+        status: None,
+    })
+}
+
+/// asks the user for the plots to be farmed, at least one plot is required
+fn get_plots_from_user_inputs() -> Result<Vec<PlotConfig>> {
+    let mut plots: Vec<PlotConfig> = vec![];
+
+    loop {
+        let plot_id = plots.len();
+
+        // the default location is only offered for the first plot
+        let default_plot_loc = plots.is_empty().then(plot_directory_getter);
+        let prompt = match &default_plot_loc {
+            Some(default_plot_loc) => format!(
+                "Specify a path for storing plot files (press enter to use the default: \
+                 `{default_plot_loc:?}`): ",
+            ),
+            None => format!("Specify a path for storing the files of plot #{plot_id}: "),
+        };
+        let directory = get_user_input(&prompt, default_plot_loc, |location| {
+            let directory = directory_parser(location)?;
+            if plots.iter().any(|plot| plot.directory == directory) {
+                return Err(eyre!("this directory is already used by another plot!"));
+            }
+            Ok(directory)
+        })?;
+
+        // get plot size
+        let size = get_user_input(
+            &format!(
+                "Specify a size for plot #{plot_id} (defaults to `{DEFAULT_PLOT_SIZE}`, press \
+                 enter to use the default): "
+            ),
+            Some(DEFAULT_PLOT_SIZE),
+            size_parser,
+        )?;
+
+        plots.push(PlotConfig { directory, size });
+
+        let prompt = "Do you want to add another plot? [y/n]: ";
+        if !get_user_input(prompt, None, yes_or_no_parser).context("prompt failed")? {
+            return Ok(plots);
+        }
+    }
+}
+
+/// builds the plots from `--plot-dir` and `--plot-size` arguments
+///
+/// the default plot location is used if no directory is given, and the
+/// default plot size is used if no size is given
+fn get_plots_from_args(
+    plot_dirs: Vec<PathBuf>,
+    plot_sizes: Vec<ByteSize>,
+    create_dirs: bool,
+) -> Result<Vec<PlotConfig>> {
+    let plot_dirs = if plot_dirs.is_empty() { vec![plot_directory_getter()] } else { plot_dirs };
+    let plot_sizes = match plot_sizes.len() {
+        0 => vec![DEFAULT_PLOT_SIZE; plot_dirs.len()],
+        1 => vec![plot_sizes[0]; plot_dirs.len()],
+        n if n == plot_dirs.len() => plot_sizes,
+        n =>
+            return Err(eyre!(
+                "got {n} plot sizes for {} plot directories, please supply either a single \
+                 `--plot-size` or one for each `--plot-dir`",
+                plot_dirs.len()
+            )),
+    };
+
+    let mut seen_directories = HashSet::new();
+    plot_dirs
+        .into_iter()
+        .zip(plot_sizes)
+        .map(|(directory, size)| {
+            let directory = init_directory(&directory, create_dirs)?;
+            if !seen_directories.insert(directory.clone()) {
+                return Err(eyre!(
+                    "plot directory `{}` is supplied more than once!",
+                    directory.display()
+                ));
+            }
+            Ok(PlotConfig { directory, size })
+        })
+        .collect()
+}
+
+/// checks the directory given with a flag, it is only created with
+/// `--create-dirs`
+fn init_directory(path: &Path, create_dirs: bool) -> Result<PathBuf> {
+    existing_directory_parser(path, create_dirs)
+        .suggestion("Create it, or supply `--create-dirs` to let the CLI create it.")
+}
+
+/// error for a value which is required when prompting is disabled
+fn missing_argument(flag: &str) -> color_eyre::eyre::Report {
+    eyre!("`{flag}` is required when running `init` without prompts (`--yes`)")
 }
@@ -0,0 +1,40 @@
+use clap::Subcommand;
+use color_eyre::eyre::{Context, Result};
+use single_instance::SingleInstance;
+
+use crate::config::{config_root, CONFIG_FILE};
+use crate::profile::{profile, profile_names, Profile};
+
+/// subcommands of the `profiles` command
+#[derive(Debug, Default, Subcommand)]
+pub(crate) enum ProfilesCommand {
+    /// lists the profiles having a config file, and whether they are farming
+    #[default]
+    List,
+}
+
+/// implementation of the `profiles` command
+pub(crate) fn profiles(command: ProfilesCommand) -> Result<()> {
+    match command {
+        ProfilesCommand::List => {
+            let config_root = config_root();
+            let names = profile_names(&config_root, CONFIG_FILE)?;
+            if names.is_empty() {
+                println!("There are no profiles yet, create one with the `init` command.");
+            }
+
+            for name in names {
+                let listed = Profile::named(&name);
+                let instance = SingleInstance::new(&listed.instance_lock())
+                    .context("failed to initialize single instance")?;
+                let status = if instance.is_single() { "idle" } else { "farming" };
+                let selected = if listed.name() == profile().name() { "*" } else { " " };
+                let config_path = listed.namespace(config_root.clone()).join(CONFIG_FILE);
+
+                println!("{selected} {name} ({status}): `{}`", config_path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
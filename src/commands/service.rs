@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use clap::Subcommand;
+use color_eyre::eyre::{eyre, Context, Result};
+
+use crate::config::{config_path, CONFIG_FILE};
+use crate::home::data_dir;
+use crate::profile::{profile, DEFAULT_PROFILE};
+
+/// directory of the system units
+const SYSTEM_UNIT_DIR: &str = "/etc/systemd/system";
+
+/// subcommands of the `service` command
+#[derive(Debug, Subcommand)]
+pub(crate) enum ServiceCommand {
+    /// writes a systemd unit running `farm` with the current binary and config
+    Install {
+        /// installs a unit of the user's service manager, instead of a system
+        /// unit (which needs root)
+        #[arg(long, action)]
+        user: bool,
+        /// user running the system unit, by default the one who ran `sudo` (or
+        /// the current one)
+        #[arg(long, value_name = "USER", conflicts_with = "user")]
+        run_as: Option<String>,
+        /// replaces an existing unit
+        #[arg(long, action)]
+        force: bool,
+    },
+}
+
+/// implementation of the `service` command
+pub(crate) fn service(command: ServiceCommand) -> Result<()> {
+    match command {
+        ServiceCommand::Install { user, run_as, force } => install(user, run_as, force),
+    }
+}
+
+/// writes the unit of the selected profile
+fn install(is_user: bool, run_as: Option<String>, force: bool) -> Result<()> {
+    // a system unit runs the farm of the installing user, with their config, even
+    // if they install it with `sudo`
+    let user = (!is_user).then(|| unit_user(run_as, std::env::var("SUDO_USER").ok()));
+    let config_path = unit_config_path(user.as_deref())?;
+    if !config_path.exists() {
+        return Err(eyre!(
+            "There is no config file at `{}`, create one with the `init` command first",
+            config_path.display()
+        ));
+    }
+    let config_path = config_path.canonicalize().context("couldn't resolve the config path")?;
+    let exe = std::env::current_exe().context("couldn't find the current binary")?;
+    // the unit does not run in the current directory
+    let data_dir = data_dir()
+        .map(|data_dir| {
+            data_dir.canonicalize().wrap_err_with(|| {
+                format!("couldn't resolve the data directory `{}`", data_dir.display())
+            })
+        })
+        .transpose()?;
+    let supplied_config_path = profile().config_path().map(|_| config_path.as_path());
+    let args = farm_args(&exe, supplied_config_path, data_dir.as_deref())?;
+
+    let unit_dir = match is_user {
+        true => dirs::config_dir()
+            .expect("couldn't get the default config directory!")
+            .join("systemd/user"),
+        false => PathBuf::from(SYSTEM_UNIT_DIR),
+    };
+    let unit_path = unit_dir.join(unit_name(profile().name()));
+    if unit_path.exists() && !force {
+        return Err(eyre!(
+            "The unit `{}` already exists, replace it with `--force`",
+            unit_path.display()
+        ));
+    }
+
+    std::fs::create_dir_all(&unit_dir)
+        .and_then(|()| std::fs::write(&unit_path, render_unit(&args, user.as_deref())))
+        .wrap_err_with(|| match is_user {
+            true => format!("couldn't write `{}`", unit_path.display()),
+            false => format!(
+                "couldn't write `{}`, a system unit needs root (or install a user unit with \
+                 `--user`)",
+                unit_path.display()
+            ),
+        })?;
+
+    let systemctl = if is_user { "systemctl --user" } else { "systemctl" };
+    let unit_name = unit_path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    match &user {
+        Some(user) => println!(
+            "Wrote the unit to `{}`, running as `{user}`, start the farm with:",
+            unit_path.display()
+        ),
+        None => println!("Wrote the unit to `{}`, start the farm with:", unit_path.display()),
+    }
+    println!("  {systemctl} daemon-reload");
+    println!("  {systemctl} enable --now {unit_name}");
+    if is_user {
+        println!("and keep it running after you log out with `loginctl enable-linger`");
+    }
+
+    Ok(())
+}
+
+/// returns the user running a system unit, the given one, or the one who ran
+/// `sudo`, or else the current one
+pub(crate) fn unit_user(run_as: Option<String>, sudo_user: Option<String>) -> String {
+    run_as
+        .or(sudo_user.filter(|user| !user.is_empty() && user != "root"))
+        .unwrap_or_else(whoami::username)
+}
+
+/// returns the config of the selected profile, for the given user running the
+/// unit
+///
+/// the default config is in the home of the user, which is not the one of the
+/// installing user under `sudo`
+fn unit_config_path(user: Option<&str>) -> Result<PathBuf> {
+    match user {
+        Some(user)
+            if user != whoami::username()
+                && profile().config_path().is_none()
+                && data_dir().is_none() =>
+        {
+            // the default config directory of the OS, see `config_root`
+            let config_root = home_dir(user)?.join(".config").join("subspace-cli");
+            Ok(profile().namespace(config_root).join(CONFIG_FILE))
+        }
+        _ => Ok(config_path()),
+    }
+}
+
+/// returns the home directory of the given user
+fn home_dir(user: &str) -> Result<PathBuf> {
+    let output = Command::new("getent")
+        .args(["passwd", user])
+        .output()
+        .context("couldn't look up the users")?;
+    // entries are `name:password:uid:gid:gecos:home:shell`
+    String::from_utf8_lossy(&output.stdout)
+        .split(':')
+        .nth(5)
+        .filter(|home| output.status.success() && !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| eyre!("There is no user `{user}`"))
+}
+
+/// returns the name of the unit of the given profile
+pub(crate) fn unit_name(profile_name: &str) -> String {
+    match profile_name {
+        DEFAULT_PROFILE => "subspace-cli.service".to_owned(),
+        name => format!("subspace-cli-{name}.service"),
+    }
+}
+
+/// returns the command line farming with the given binary, config and data
+/// directory, for the selected profile
+///
+/// the config is only given if it was supplied with `--config`, as it is a
+/// farm of its own (see [`crate::profile`])
+pub(crate) fn farm_args(
+    exe: &Path,
+    config_path: Option<&Path>,
+    data_dir: Option<&Path>,
+) -> Result<Vec<String>> {
+    let path_arg = |path: &Path| {
+        path.to_str()
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| eyre!("`{}` is not a valid UTF-8 path", path.display()))
+    };
+
+    let mut args = vec![path_arg(exe)?];
+    if profile().name() != DEFAULT_PROFILE {
+        args.extend(["--profile".to_owned(), profile().name().to_owned()]);
+    }
+    if let Some(config_path) = config_path {
+        args.extend(["--config".to_owned(), path_arg(config_path)?]);
+    }
+    if let Some(data_dir) = data_dir {
+        args.extend(["--data-dir".to_owned(), path_arg(data_dir)?]);
+    }
+    args.extend(["farm", "--output", "json"].map(ToOwned::to_owned));
+    Ok(args)
+}
+
+/// returns the unit running the given command line, as the given user for a
+/// system unit (`None` for a unit of the user's service manager)
+pub(crate) fn render_unit(args: &[String], user: Option<&str>) -> String {
+    let exec_start = args.iter().map(|arg| quote(arg)).collect::<Vec<_>>().join(" ");
+    let user = user.map(|user| format!("User={user}\n")).unwrap_or_default();
+    let wanted_by = if user.is_empty() { "default.target" } else { "multi-user.target" };
+
+    format!(
+        "[Unit]
+Description=Subspace farmer
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=notify
+ExecStart={exec_start}
+{user}Restart=on-failure
+RestartSec=10
+# the node is synced before the farm is ready, which takes a while
+TimeoutStartSec=infinity
+WatchdogSec=300
+# `farm` shuts down gracefully on ctrl+c
+KillSignal=SIGINT
+TimeoutStopSec=120
+
+[Install]
+WantedBy={wanted_by}
+"
+    )
+}
+
+/// quotes the given argument of `ExecStart`, see `systemd.service(5)`
+fn quote(arg: &str) -> String {
+    // specifiers and environment variables are expanded by systemd
+    let arg = arg.replace('%', "%%").replace('$', "$$");
+    let is_plain = !arg.is_empty()
+        && !arg.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'));
+    match is_plain {
+        true => arg,
+        false => format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
@@ -1,10 +1,13 @@
+use std::path::{Path, PathBuf};
+
 use color_eyre::eyre::{Context, Result};
 use owo_colors::OwoColorize;
 use subspace_sdk::farmer::CacheDescription;
-use subspace_sdk::{Node, PlotDescription};
+use subspace_sdk::{ByteSize, Node, PlotDescription};
 
-use crate::config::{delete_config, parse_config};
-use crate::summary::delete_summary;
+use crate::config::preflight::normalize;
+use crate::config::{delete_config, parse_config, PlotConfig};
+use crate::summary::{delete_summaries, summary_dir};
 use crate::utils::{
     cache_directory_getter, get_user_input, node_directory_getter, plot_directory_getter,
     yes_or_no_parser,
@@ -13,9 +16,9 @@ use crate::utils::{
 /// wipe configurator
 ///
 /// sets the `farmer`, `node`, `summary`, and `config` flags for the `wipe`
-/// command
-pub(crate) async fn wipe_config(farmer: bool, node: bool) -> Result<()> {
-    if !farmer && !node {
+/// command, `plots` can be used to wipe only some of the plots
+pub(crate) async fn wipe_config(farmer: bool, node: bool, plots: Vec<PathBuf>) -> Result<()> {
+    if !farmer && !node && plots.is_empty() {
         // if user did not supply any argument, ask for everything
         let prompt = "Do you want to wipe farmer (delete plot)? [y/n]: ";
         let wipe_farmer =
@@ -34,6 +37,10 @@ pub(crate) async fn wipe_config(farmer: bool, node: bool) -> Result<()> {
 
         wipe(wipe_farmer, wipe_node, wipe_summary, wipe_config).await?;
     } else {
+        // wiping all the plots already covers the individual ones
+        if !farmer && !plots.is_empty() {
+            wipe_plots(plots).await?;
+        }
         // don't delete summary and config if user supplied flags
         wipe(farmer, node, false, false).await?;
     }
@@ -50,51 +57,62 @@ async fn wipe(
     wipe_summary: bool,
     wipe_config: bool,
 ) -> Result<()> {
+    // the locations in the config are used if it can be read, else, the default
+    // locations are wiped
+    let config = match parse_config() {
+        Ok(config) => Some(config),
+        Err(_) if wipe_farmer || wipe_node || wipe_summary => {
+            println!(
+                "could not read your config. Wipe will still continue... \n{}",
+                "However, if you have set custom locations for your plots, node, cache or \
+                 summary, you will need to manually delete them!"
+                    .underline()
+            );
+            None
+        }
+        Err(_) => None,
+    };
+
     if wipe_node {
         println!("wiping node...");
-        let node_directory = node_directory_getter();
-        let _ = Node::wipe(node_directory).await;
+        match &config {
+            Some(config) => {
+                let _ = Node::wipe(&config.node.directory).await;
+                // the provider storage is inside the node directory by default, but it may
+                // have been moved
+                let _ = tokio::fs::remove_dir_all(&config.node.provider_storage_directory).await;
+            }
+            None => {
+                let _ = Node::wipe(node_directory_getter()).await;
+            }
+        }
     }
 
     if wipe_farmer {
         println!("wiping farmer...");
-        let config = match parse_config() {
-            Ok(args) => Some(args),
-            Err(_) => {
-                println!(
-                    "could not read your config. Wipe will still continue... \n{}",
-                    "However, if you have set a custom location for your plots, you will need to \
-                     manually delete your plots!"
-                        .underline()
-                );
-                None
-            }
-        };
-
-        // TODO: modify here when supporting multi-plot
-        // if config can be read, delete the farmer using the path in the config, else,
-        // delete the default location
-        if let Some(config) = config {
-            match PlotDescription::new(config.farmer.plot_directory, config.farmer.plot_size) {
-                Ok(plot) => {
-                    let _ = plot.wipe().await;
-                }
-                Err(err) => println!(
-                    "Skipping wiping plot. Got error while constructing the plot reference: {err}"
-                ),
+
+        if let Some(config) = &config {
+            for PlotConfig { directory, size } in config.farmer.plots.clone() {
+                wipe_plot(directory, size).await;
             }
-            let _ =
-                CacheDescription::new(cache_directory_getter(), config.farmer.advanced.cache_size)?
-                    .wipe()
-                    .await;
+            let _ = CacheDescription::new(
+                config.farmer.cache_directory.clone(),
+                config.farmer.advanced.cache_size,
+            )?
+            .wipe()
+            .await;
         } else {
             let _ = tokio::fs::remove_dir_all(plot_directory_getter()).await;
+            let _ = tokio::fs::remove_dir_all(cache_directory_getter()).await;
         }
     }
 
     if wipe_summary {
-        match delete_summary() {
-            Ok(_) => println!("deleted the summary file"),
+        let summary_dir = config
+            .as_ref()
+            .map_or_else(summary_dir, |config| config.farmer.summary_directory.clone());
+        match delete_summaries(&summary_dir) {
+            Ok(_) => println!("deleted the summaries"),
             Err(_) => println!("Skipping wiping summary, could not find the file..."),
         }
     }
@@ -110,3 +128,41 @@ async fn wipe(
 
     Ok(())
 }
+
+/// wipes only the given plots, which should be present in the config
+async fn wipe_plots(directories: Vec<PathBuf>) -> Result<()> {
+    let config = parse_config().context("couldn't read your config to find the plots")?;
+
+    for directory in directories {
+        match find_plot(&config.farmer.plots, &directory) {
+            Some(plot) => {
+                println!("wiping plot at `{}`...", plot.directory.display());
+                wipe_plot(plot.directory.clone(), plot.size).await;
+            }
+            None => println!(
+                "Skipping wiping plot, `{}` is not one of the plots in your config...",
+                directory.display()
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// returns the plot of the given directory, however the directory is spelled
+/// (i.e. relative, or through a symlink)
+pub(crate) fn find_plot<'a>(plots: &'a [PlotConfig], directory: &Path) -> Option<&'a PlotConfig> {
+    let directory = normalize(directory);
+    plots.iter().find(|plot| normalize(&plot.directory) == directory)
+}
+
+async fn wipe_plot(directory: PathBuf, size: ByteSize) {
+    match PlotDescription::new(directory, size) {
+        Ok(plot) => {
+            let _ = plot.wipe().await;
+        }
+        Err(err) => {
+            println!("Skipping wiping plot. Got error while constructing the plot reference: {err}")
+        }
+    }
+}
@@ -1,28 +1,90 @@
 use std::fs::{create_dir_all, remove_file, File};
-use std::path::PathBuf;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use derivative::Derivative;
+use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
+use subspace_sdk::chain_spec::{self, ChainSpec};
 use subspace_sdk::farmer::{CacheDescription, Farmer};
 use subspace_sdk::node::domains::core_payments::ConfigBuilder;
 use subspace_sdk::node::{domains, DsnBuilder, NetworkBuilder, Node, Role};
-use subspace_sdk::{chain_spec, ByteSize, PlotDescription, PublicKey};
+use subspace_sdk::{ByteSize, PlotDescription, PublicKey};
 use tracing::instrument;
 
+use self::layers::ConfigSources;
+pub(crate) use self::migration::CONFIG_VERSION;
+use self::preflight::{check_plots, preflight, PreflightReport};
+use crate::home::{data_dir_or, profile_home};
+use crate::output::print_line;
+use crate::profile::profile;
+use crate::summary::storage::StorageKind;
+use crate::summary::summary_dir;
 use crate::utils::{cache_directory_getter, provider_storage_dir_getter, IntoEyre};
 
+pub(crate) mod keys;
+pub(crate) mod layers;
+pub(crate) mod migration;
+pub(crate) mod preflight;
+
 /// defaults for the user config file
 pub(crate) const DEFAULT_PLOT_SIZE: ByteSize = ByteSize::gb(1);
 pub(crate) const MIN_PLOT_SIZE: ByteSize = ByteSize::mib(32);
 
+/// name of the config file
+pub(crate) const CONFIG_FILE: &str = "settings.toml";
+
 /// structure of the config toml file
 #[derive(Deserialize, Serialize, Debug)]
 pub(crate) struct Config {
+    /// version of the config schema, see [`migration`]
+    pub(crate) version: u32,
     pub(crate) chain: ChainConfig,
     pub(crate) farmer: FarmerConfig,
     pub(crate) node: NodeConfig,
+    /// metrics are only served with a `[metrics]` section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) metrics: Option<MetricsConfig>,
+    /// the status API is only served with a `[status]` section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) status: Option<StatusConfig>,
+}
+
+impl Config {
+    /// resolves the relative paths of the config against the given directory
+    pub(crate) fn resolve_paths(&mut self, root: &Path) {
+        self.map_paths(|path| root.join(path));
+    }
+
+    /// makes the paths inside the given directory relative to it, so that the
+    /// directory can be moved along with the config
+    pub(crate) fn relativize_paths(&mut self, root: &Path) {
+        self.map_paths(|path| match path.strip_prefix(root) {
+            Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(relative) => relative.to_owned(),
+            Err(_) => path.to_owned(),
+        });
+    }
+
+    /// replaces every path of the config with the result of `f`
+    fn map_paths(&mut self, f: impl Fn(&Path) -> PathBuf) {
+        let Config { chain, farmer, node, .. } = self;
+
+        for plot in &mut farmer.plots {
+            plot.directory = f(&plot.directory);
+        }
+        farmer.cache_directory = f(&farmer.cache_directory);
+        farmer.summary_directory = f(&farmer.summary_directory);
+        node.directory = f(&node.directory);
+        node.provider_storage_directory = f(&node.provider_storage_directory);
+        if let ChainConfig::Custom(custom) = chain {
+            custom.custom = f(&custom.custom);
+        }
+    }
 }
 
 /// Advanced Node Settings Wrapper for CLI
@@ -39,19 +101,27 @@ pub(crate) struct AdvancedNodeSettings {
 pub(crate) struct NodeConfig {
     pub(crate) directory: PathBuf,
     pub(crate) name: String,
+    /// directory of the DSN provider storage
+    #[serde(default = "provider_storage_dir_getter")]
+    pub(crate) provider_storage_directory: PathBuf,
     #[serde(default, skip_serializing_if = "crate::utils::is_default")]
     pub(crate) advanced: AdvancedNodeSettings,
 }
 
 impl NodeConfig {
     pub async fn build(self, chain: ChainConfig, is_verbose: bool) -> Result<Node> {
-        let Self { directory, name, advanced: AdvancedNodeSettings { executor, extra } } = self;
+        let Self {
+            directory,
+            name,
+            provider_storage_directory,
+            advanced: AdvancedNodeSettings { executor, extra },
+        } = self;
 
         let (mut node, chain_spec) = match chain {
             ChainConfig::Gemini3d => {
-                let node = Node::gemini_3d().network(NetworkBuilder::gemini_3d().name(name)).dsn(
-                    DsnBuilder::gemini_3d().provider_storage_path(provider_storage_dir_getter()),
-                );
+                let node = Node::gemini_3d()
+                    .network(NetworkBuilder::gemini_3d().name(name))
+                    .dsn(DsnBuilder::gemini_3d().provider_storage_path(provider_storage_directory));
                 let chain_spec = chain_spec::gemini_3d();
                 (node, chain_spec)
             }
@@ -63,10 +133,31 @@ impl NodeConfig {
             ChainConfig::DevNet => {
                 let node = Node::devnet()
                     .network(NetworkBuilder::devnet().name(name))
-                    .dsn(DsnBuilder::devnet().provider_storage_path(provider_storage_dir_getter()));
+                    .dsn(DsnBuilder::devnet().provider_storage_path(provider_storage_directory));
                 let chain_spec = chain_spec::devnet_config();
                 (node, chain_spec)
             }
+            ChainConfig::Custom(CustomChainConfig {
+                custom,
+                bootstrap_nodes,
+                dsn_bootstrap_nodes,
+            }) => {
+                let node = Node::builder()
+                    .network(
+                        NetworkBuilder::new()
+                            .name(name)
+                            .boot_nodes(parse_addresses(&bootstrap_nodes)?),
+                    )
+                    .dsn(
+                        DsnBuilder::new()
+                            .provider_storage_path(provider_storage_directory)
+                            .boot_nodes(parse_addresses(&dsn_bootstrap_nodes)?),
+                    );
+                let chain_spec = ChainSpec::from_json_file(custom.clone()).map_err(|err| {
+                    eyre!("couldn't load the chain spec `{}`: {err}", custom.display())
+                })?;
+                (node, chain_spec)
+            }
         };
 
         if executor {
@@ -97,6 +188,20 @@ impl NodeConfig {
     }
 }
 
+/// parses the multiaddresses of bootstrap nodes
+fn parse_addresses<T>(addresses: &[String]) -> Result<Vec<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    addresses
+        .iter()
+        .map(|address| {
+            address.parse().map_err(|err| eyre!("invalid bootstrap node `{address}`: {err}"))
+        })
+        .collect()
+}
+
 /// Advanced Farmer Settings Wrapper for CLI
 #[derive(Deserialize, Serialize, Clone, Derivative, Debug, PartialEq)]
 #[derivative(Default)]
@@ -108,51 +213,176 @@ pub(crate) struct AdvancedFarmerSettings {
     pub(crate) extra: toml::Table,
 }
 
+/// Prometheus endpoint of the farm, see [`crate::metrics`]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Derivative)]
+#[derivative(Default)]
+pub(crate) struct MetricsConfig {
+    /// address to serve the metrics at
+    #[derivative(Default(value = "([127, 0, 0, 1], 9616).into()"))]
+    #[serde(default = "default_metrics_address")]
+    pub(crate) address: SocketAddr,
+}
+
+/// returns the default address of the metrics
+fn default_metrics_address() -> SocketAddr {
+    MetricsConfig::default().address
+}
+
+/// local JSON status API of the farm, see [`crate::status`]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Derivative)]
+#[derivative(Default)]
+pub(crate) struct StatusConfig {
+    /// address to serve the status API at
+    #[derivative(Default(value = "([127, 0, 0, 1], 9617).into()"))]
+    #[serde(default = "default_status_address")]
+    pub(crate) address: SocketAddr,
+}
+
+/// returns the default address of the status API
+fn default_status_address() -> SocketAddr {
+    StatusConfig::default().address
+}
+
+/// Plot Options Wrapper for CLI
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct PlotConfig {
+    pub(crate) directory: PathBuf,
+    pub(crate) size: ByteSize,
+}
+
 /// Farmer Options Wrapper for CLI
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub(crate) struct FarmerConfig {
     pub(crate) reward_address: PublicKey,
-    pub(crate) plot_directory: PathBuf,
-    pub(crate) plot_size: ByteSize,
+    pub(crate) plots: Vec<PlotConfig>,
+    /// directory of the piece cache
+    #[serde(default = "cache_directory_getter")]
+    pub(crate) cache_directory: PathBuf,
+    /// directory of the summary of the farming, see [`crate::summary`]
+    #[serde(default = "summary_dir")]
+    pub(crate) summary_directory: PathBuf,
+    /// storage of the summary, see [`crate::summary::storage`]
+    #[serde(default, skip_serializing_if = "crate::utils::is_default")]
+    pub(crate) summary_storage: StorageKind,
     #[serde(default, skip_serializing_if = "crate::utils::is_default")]
     pub(crate) advanced: AdvancedFarmerSettings,
 }
 
 impl FarmerConfig {
     pub async fn build(self, node: &Node) -> Result<Farmer> {
-        let plot_description = &[PlotDescription::new(self.plot_directory, self.plot_size)
-            .wrap_err("Plot size is too low")?];
-        let cache = CacheDescription::new(cache_directory_getter(), self.advanced.cache_size)?;
+        let plot_descriptions = self
+            .plots
+            .into_iter()
+            .map(|PlotConfig { directory, size }| {
+                PlotDescription::new(directory, size).wrap_err("Plot size is too low")
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let cache = CacheDescription::new(self.cache_directory, self.advanced.cache_size)?;
 
         // currently we do not have different configuration for the farmer w.r.t
         // different chains, but we may in the future
         let farmer = Farmer::builder();
         crate::utils::apply_extra_options(&farmer.configuration(), self.advanced.extra)
             .context("Failed to deserialize node config")?
-            .build(self.reward_address, node, plot_description, cache)
+            .build(self.reward_address, node, &plot_descriptions, cache)
             .await
             .context("Failed to build a farmer")
     }
+
+    /// sum of the sizes of all the plots
+    pub(crate) fn total_plot_size(&self) -> ByteSize {
+        ByteSize::b(self.plots.iter().map(|plot| plot.size.as_u64()).sum())
+    }
 }
 
 /// Enum for Chain
 #[derive(Deserialize, Serialize, Default, Clone, Debug, EnumIter)]
+#[serde(try_from = "ChainConfigRepr", into = "ChainConfigRepr")]
 pub(crate) enum ChainConfig {
     #[default]
     Gemini3d,
     Dev,
     DevNet,
+    /// private network, built from a chain spec file
+    Custom(CustomChainConfig),
+}
+
+impl ChainConfig {
+    /// returns the chains known by the CLI, without the custom one
+    pub(crate) fn known() -> impl Iterator<Item = ChainConfig> {
+        ChainConfig::iter().filter(|chain| !matches!(chain, ChainConfig::Custom(_)))
+    }
+
+    /// returns the known chain with the given name, case insensitive
+    fn from_name(name: &str) -> Option<ChainConfig> {
+        match name.to_lowercase().as_str() {
+            "gemini3d" => Some(ChainConfig::Gemini3d),
+            "dev" => Some(ChainConfig::Dev),
+            "devnet" => Some(ChainConfig::DevNet),
+            _ => None,
+        }
+    }
 }
 
 impl std::str::FromStr for ChainConfig {
     type Err = Report;
 
+    /// parses either the name of a known chain, or the path of a chain spec
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "gemini3d" => Ok(ChainConfig::Gemini3d),
-            "dev" => Ok(ChainConfig::Dev),
-            "devnet" => Ok(ChainConfig::DevNet),
-            _ => Err(eyre!("given chain: `{s}` is not recognized!")),
+        if let Some(chain) = ChainConfig::from_name(s) {
+            return Ok(chain);
+        }
+        if !Path::new(s).is_file() {
+            return Err(eyre!("given chain: `{s}` is not recognized, nor a chain spec file!"));
+        }
+
+        // the node may be started from another directory, so the path is stored
+        // as absolute
+        let custom = std::fs::canonicalize(s)
+            .wrap_err_with(|| format!("couldn't resolve the path of the chain spec `{s}`"))?;
+        Ok(ChainConfig::Custom(CustomChainConfig { custom, ..Default::default() }))
+    }
+}
+
+/// chain spec and bootstrap nodes of a private network
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CustomChainConfig {
+    /// path of the chain spec JSON file
+    pub(crate) custom: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) bootstrap_nodes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) dsn_bootstrap_nodes: Vec<String>,
+}
+
+/// representation of [`ChainConfig`] in the config file, either the name of a
+/// known chain (i.e. `chain = "Gemini3d"`), or a custom chain table (i.e.
+/// `chain = { custom = "/path/to/chainspec.json" }`)
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum ChainConfigRepr {
+    Named(String),
+    Custom(CustomChainConfig),
+}
+
+impl TryFrom<ChainConfigRepr> for ChainConfig {
+    type Error = Report;
+
+    fn try_from(repr: ChainConfigRepr) -> Result<Self> {
+        match repr {
+            ChainConfigRepr::Named(name) => ChainConfig::from_name(&name)
+                .ok_or_else(|| eyre!("given chain: `{name}` is not recognized!")),
+            ChainConfigRepr::Custom(custom) => Ok(ChainConfig::Custom(custom)),
+        }
+    }
+}
+
+impl From<ChainConfig> for ChainConfigRepr {
+    fn from(chain: ChainConfig) -> Self {
+        match chain {
+            ChainConfig::Custom(custom) => ChainConfigRepr::Custom(custom),
+            chain => ChainConfigRepr::Named(format!("{chain:?}")),
         }
     }
 }
@@ -161,47 +391,172 @@ impl std::str::FromStr for ChainConfig {
 /// - **Linux:** `$HOME/.config/subspace-cli/settings.toml`.
 /// - **macOS:** `$HOME/Library/Application Support/subspace-cli/settings.toml`.
 /// - **Windows:** `{FOLDERID_RoamingAppData}/subspace-cli/settings.toml`.
+///
+/// named profiles keep their config in `subspace-cli/profiles/<name>/`, see
+/// [`crate::profile`]
 pub(crate) fn create_config() -> Result<(File, PathBuf)> {
-    let config_path = dirs::config_dir()
-        .expect("couldn't get the default config directory!")
-        .join("subspace-cli");
+    let config_path = config_path();
 
-    if let Err(err) = create_dir_all(&config_path) {
-        let config_path = config_path.to_str().expect("couldn't get subspace-cli config path!");
-        return Err(err).wrap_err(format!("could not create the directory: `{config_path}`"));
+    if let Some(config_dir) = config_path.parent() {
+        if let Err(err) = create_dir_all(config_dir) {
+            let config_dir = config_dir.to_str().expect("couldn't get subspace-cli config path!");
+            return Err(err).wrap_err(format!("could not create the directory: `{config_dir}`"));
+        }
     }
 
-    let file = File::create(config_path.join("settings.toml"))?;
+    let file = File::create(&config_path)?;
 
     Ok((file, config_path))
 }
 
 /// parses the config, and returns [`Config`]
+///
+/// environment variables and `--set` arguments override the values of the
+/// config file, see [`layers`]
 #[instrument]
 pub(crate) fn parse_config() -> Result<Config> {
-    let config_path = dirs::config_dir().expect("couldn't get the default config directory!");
-    let config_path = config_path.join("subspace-cli").join("settings.toml");
+    parse_layered_config().map(|(config, _)| config)
+}
 
-    let config: Config = toml::from_str(&std::fs::read_to_string(config_path)?)?;
-    Ok(config)
+/// parses the config like [`parse_config`], and also returns the layer each
+/// value comes from
+#[instrument]
+pub(crate) fn parse_layered_config() -> Result<(Config, ConfigSources)> {
+    let (table, sources) = layers::apply_overrides(parse_config_table()?)?;
+    let mut config: Config =
+        toml::Value::Table(table).try_into().context("couldn't parse the config file")?;
+    if let Some(profile_home) = profile_home() {
+        config.resolve_paths(&profile_home);
+    }
+    Ok((config, sources))
 }
 
-/// validates the config for farming
+/// reads the config file as a raw toml table
+///
+/// if the config file is from an older version of the CLI, it is upgraded in
+/// place, and the original file is kept as a backup next to it
+#[instrument]
+pub(crate) fn parse_config_table() -> Result<toml::Table> {
+    let config_path = config_path();
+
+    let mut table: toml::Table = toml::from_str(
+        &std::fs::read_to_string(&config_path).context("couldn't read the config file")?,
+    )
+    .context("config file is not a valid toml file")?;
+
+    if let Some(old_version) = migration::migrate(&mut table)? {
+        let backup_path = config_path.with_extension(format!("toml.v{old_version}.bak"));
+        std::fs::copy(&config_path, &backup_path)
+            .context("couldn't create a backup of the config file")?;
+        replace_config_file(
+            &config_path,
+            &toml::to_string_pretty(&table).context("Failed to serialize the migrated config")?,
+        )
+        .context("couldn't write the migrated config")?;
+        print_line(format!(
+            "Your config is upgraded to the latest version, the old one is kept at `{}`",
+            backup_path.display()
+        ));
+    }
+
+    Ok(table)
+}
+
+/// checks whether the config file was upgraded from the one of an older
+/// version farming on the given chain with the given reward address, i.e.
+/// whether the unscoped summary of the older version was farmed for them
 #[instrument]
-pub(crate) fn validate_config() -> Result<Config> {
-    let config = parse_config()?;
+pub(crate) fn is_original_config(chain: &ChainConfig, reward_address: &PublicKey) -> bool {
+    let config_path = config_path();
+    // the oldest backup is the config the older version farmed with
+    let Some(mut table) = (0..CONFIG_VERSION)
+        .map(|version| config_path.with_extension(format!("toml.v{version}.bak")))
+        .find_map(|backup_path| std::fs::read_to_string(backup_path).ok())
+        .and_then(|backup| toml::from_str::<toml::Table>(&backup).ok())
+    else {
+        return false;
+    };
+    if migration::migrate(&mut table).is_err() {
+        return false;
+    }
 
-    // validity checks
-    if config.farmer.plot_size < MIN_PLOT_SIZE {
-        return Err(eyre!("plot size should be bigger than {MIN_PLOT_SIZE}!"));
+    match toml::Value::Table(table).try_into::<Config>() {
+        Ok(original) =>
+            original.farmer.reward_address.to_string() == reward_address.to_string()
+                && toml::Value::try_from(&original.chain).ok() == toml::Value::try_from(chain).ok(),
+        Err(_) => false,
     }
+}
+
+/// overwrites the existing config file with the given [`Config`]
+#[instrument]
+pub(crate) fn write_config(config: &Config) -> Result<()> {
+    let config_text = toml::to_string_pretty(config).context("Failed to serialize config")?;
+    replace_config_file(&config_path(), &config_text)
+}
+
+/// replaces the given config file with the given text, through a temporary
+/// file synced to the disk, so that the config is never left half written
+pub(crate) fn replace_config_file(config_path: &Path, config_text: &str) -> Result<()> {
+    let temp_path = config_path.with_extension("toml.tmp");
+    let mut temp_file =
+        File::create(&temp_path).context("couldn't create temporary config file")?;
+    temp_file.write_all(config_text.as_bytes()).context("couldn't write the config file")?;
+    temp_file.sync_all().context("couldn't sync the config file to the disk")?;
+    drop(temp_file);
+    std::fs::rename(&temp_path, config_path).context("couldn't replace the config file")?;
+
+    // the rename is only durable once the directory itself is synced
+    #[cfg(unix)]
+    if let Some(config_dir) = config_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(config_dir)
+            .and_then(|dir| dir.sync_all())
+            .context("couldn't sync the config directory to the disk")?;
+    }
+
+    Ok(())
+}
+
+/// returns the path of the config file of the selected profile
+pub(crate) fn config_path() -> PathBuf {
+    let profile = profile();
+    match profile.config_path() {
+        Some(config_path) => config_path.to_owned(),
+        None => profile.namespace(config_root()).join(CONFIG_FILE),
+    }
+}
+
+/// returns the config directory of the CLI (or the data directory if it is
+/// supplied), which holds the config of the default profile
+pub(crate) fn config_root() -> PathBuf {
+    data_dir_or(|| {
+        dirs::config_dir().expect("couldn't get the default config directory!").join("subspace-cli")
+    })
+}
+
+/// validates the config for farming
+///
+/// all the problems found by the [`preflight`] checks are reported together,
+/// warnings are printed, and do not prevent farming
+#[instrument]
+pub(crate) fn validate_config() -> Result<(Config, ConfigSources)> {
+    let (config, sources) = parse_layered_config()?;
+    for warning in preflight(&config).into_result()? {
+        print_line(format!("{} {warning}", "warning:".yellow()));
+    }
+    Ok((config, sources))
+}
 
-    Ok(config)
+/// checks the values of the given config, which cannot be checked while
+/// deserializing
+pub(crate) fn check_config(config: &Config) -> Result<()> {
+    let mut report = PreflightReport::default();
+    check_plots(config, &mut report);
+    report.into_result().map(drop)
 }
 
 /// deletes the config file
 #[instrument]
 pub(crate) fn delete_config() -> Result<()> {
-    let config_path = dirs::config_dir().expect("couldn't get the default config directory!");
-    remove_file(config_path).context("couldn't delete config file")
+    remove_file(config_path()).context("couldn't delete config file")
 }
@@ -0,0 +1,99 @@
+//! Dotted keys (i.e. `farmer.plots.0.size`) pointing into the config.
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use toml::{Table, Value};
+
+use super::{ChainConfig, CustomChainConfig};
+use crate::summary::storage::StorageKind;
+use crate::utils::{
+    cache_size_parser, existing_directory_parser, node_name_parser, reward_address_parser,
+    size_parser, yes_or_no_parser,
+};
+
+/// parses the given value for the dotted key
+///
+/// known keys are validated with the same parsers used by `init` (without
+/// prompting to create missing directories), other keys
+/// (i.e. the `extra` options in `advanced` sections) are parsed as toml values,
+/// and fall back to a plain string
+pub(crate) fn parse_value(key: &str, value: &str) -> Result<Value> {
+    fn to_value<T: serde::Serialize>(value: T) -> Result<Value> {
+        Value::try_from(value).context("Failed to serialize the value")
+    }
+
+    match key.split('.').collect::<Vec<_>>().as_slice() {
+        ["version"] => Err(eyre!("`version` is managed by the CLI, and cannot be set")),
+        ["chain"] => to_value(ChainConfig::from_str(value)?),
+        ["chain", "custom"] => match ChainConfig::from_str(value)? {
+            ChainConfig::Custom(CustomChainConfig { custom, .. }) => to_value(custom),
+            _ => Err(eyre!("`chain.custom` should be the path of a chain spec file!")),
+        },
+        ["farmer", "reward_address"] => to_value(reward_address_parser(value)?),
+        ["farmer", "plots", _, "directory"]
+        | ["farmer", "cache_directory" | "summary_directory"]
+        | ["node", "directory" | "provider_storage_directory"] =>
+            to_value(existing_directory_parser(Path::new(value), false)?),
+        ["farmer", "plots", _, "size"] => to_value(size_parser(value)?),
+        ["farmer", "advanced", "cache_size"] => to_value(cache_size_parser(value)?),
+        ["farmer", "summary_storage"] => to_value(
+            StorageKind::from_str(value)
+                .map_err(|_| eyre!("`summary_storage` should be either `toml` or `sqlite`"))?,
+        ),
+        [section @ ("metrics" | "status"), "address"] =>
+            to_value(value.parse::<SocketAddr>().map_err(|_| {
+                eyre!("`{section}.address` should be an address, i.e. `127.0.0.1:9616`")
+            })?),
+        ["node", "name"] => to_value(node_name_parser(value)?),
+        ["node", "advanced", "executor"] =>
+            to_value(value.parse::<bool>().or_else(|_| yes_or_no_parser(value))?),
+        _ => Ok(toml::from_str::<Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| Value::String(value.to_owned()))),
+    }
+}
+
+/// returns the value at the given dotted key, numeric parts index into arrays
+pub(crate) fn get_value<'a>(root: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.').try_fold(root, |value, part| match value {
+        Value::Table(table) => table.get(part),
+        Value::Array(array) => array.get(part.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// sets the value at the given dotted key
+///
+/// missing tables on the way are created, and an array index equal to the
+/// length of the array appends to it
+pub(crate) fn set_value(root: &mut Value, key: &str, value: Value) -> Result<()> {
+    fn set_value_inner(current: &mut Value, parts: &[&str], value: Value, key: &str) -> Result<()> {
+        let Some((part, rest)) = parts.split_first() else {
+            *current = value;
+            return Ok(());
+        };
+
+        let next = match current {
+            Value::Table(table) => table.entry(*part).or_insert_with(|| Value::Table(Table::new())),
+            Value::Array(array) => {
+                let index =
+                    part.parse::<usize>().ok().filter(|index| *index <= array.len()).ok_or_else(
+                        || eyre!("`{part}` in `{key}` should be an index up to {}", array.len()),
+                    )?;
+                if index == array.len() {
+                    array.push(Value::Table(Table::new()));
+                }
+                &mut array[index]
+            }
+            _ => return Err(eyre!("`{key}` does not point into a table or a list")),
+        };
+
+        set_value_inner(next, rest, value, key)
+    }
+
+    set_value_inner(root, &key.split('.').collect::<Vec<_>>(), value, key)
+}
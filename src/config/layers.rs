@@ -0,0 +1,138 @@
+//! Layered configuration.
+//!
+//! The effective config is the config file, overridden by environment
+//! variables (i.e. `SUBSPACE_CLI__FARMER__PLOTS__0__SIZE=2TB`), overridden by
+//! `--set key=value` arguments given on the command line.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use color_eyre::eyre::{Result, WrapErr};
+use toml::{Table, Value};
+
+use super::keys::{parse_value, set_value};
+use crate::utils::merge_tables;
+
+/// prefix of the environment variables overriding the config, nested keys
+/// are separated with `__`
+pub(crate) const ENV_PREFIX: &str = "SUBSPACE_CLI__";
+
+/// `--set key=value` arguments given on the command line
+static CLI_OVERRIDES: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+/// stores the `--set key=value` arguments, should be called once on startup
+pub(crate) fn set_cli_overrides(overrides: Vec<(String, String)>) {
+    CLI_OVERRIDES.set(overrides).expect("command line overrides are only set once on startup");
+}
+
+/// the layer a config value comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ConfigSource {
+    File,
+    Env(String),
+    Cli,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::File => write!(f, "config file"),
+            ConfigSource::Env(name) => write!(f, "environment variable `{name}`"),
+            ConfigSource::Cli => write!(f, "`--set` argument"),
+        }
+    }
+}
+
+/// the layer of every value in the effective config, by their dotted keys
+pub(crate) type ConfigSources = BTreeMap<String, ConfigSource>;
+
+/// applies the environment and the command line overrides on top of the
+/// config file
+pub(crate) fn apply_overrides(file: Table) -> Result<(Table, ConfigSources)> {
+    let cli_overrides = CLI_OVERRIDES
+        .get()
+        .into_iter()
+        .flatten()
+        .map(|(key, value)| (key.clone(), value.clone(), ConfigSource::Cli))
+        .collect();
+
+    apply_layers(file, [env_overrides(std::env::vars()), cli_overrides])
+}
+
+/// collects the overrides from the given environment variables
+///
+/// parts of the key written in uppercase (i.e. `FARMER`) are lowercased, the
+/// other ones are kept as they are, so that the case-sensitive keys of the
+/// advanced options can be set (i.e. `NODE__ADVANCED__blocks_pruning__Some`)
+pub(crate) fn env_overrides(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Vec<(String, String, ConfigSource)> {
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let key = name
+                .strip_prefix(ENV_PREFIX)?
+                .split("__")
+                .filter(|part| !part.is_empty())
+                .map(|part| match part.chars().any(char::is_lowercase) {
+                    true => part.to_owned(),
+                    false => part.to_lowercase(),
+                })
+                .collect::<Vec<_>>()
+                .join(".");
+            (!key.is_empty()).then_some((key, value, ConfigSource::Env(name)))
+        })
+        .collect()
+}
+
+/// merges the layers of overrides on top of the config file, in order
+pub(crate) fn apply_layers(
+    file: Table,
+    layers: impl IntoIterator<Item = Vec<(String, String, ConfigSource)>>,
+) -> Result<(Table, ConfigSources)> {
+    let mut sources = ConfigSources::new();
+    let mut config = file;
+    for (key, value) in &config {
+        record_sources(value, key, &ConfigSource::File, &mut sources);
+    }
+
+    for overrides in layers {
+        let mut layer = Value::Table(Table::new());
+
+        for (key, value, source) in overrides {
+            let value = parse_value(&key, &value)
+                .wrap_err_with(|| format!("invalid value for `{key}` from {source}"))?;
+
+            // the whole subtree under the key is replaced by this layer
+            sources.retain(|existing, _| {
+                existing != &key && !existing.starts_with(&format!("{key}."))
+            });
+            record_sources(&value, &key, &source, &mut sources);
+
+            set_value(&mut layer, &key, value)
+                .wrap_err_with(|| format!("invalid key `{key}` from {source}"))?;
+        }
+
+        if let Value::Table(layer) = layer {
+            merge_tables(&mut config, layer).wrap_err("couldn't apply the overrides")?;
+        }
+    }
+
+    Ok((config, sources))
+}
+
+/// records the source of every leaf value under the given key
+fn record_sources(value: &Value, key: &str, source: &ConfigSource, sources: &mut ConfigSources) {
+    match value {
+        Value::Table(table) =>
+            for (child, value) in table {
+                record_sources(value, &format!("{key}.{child}"), source, sources);
+            },
+        Value::Array(array) if !array.is_empty() && array.iter().all(Value::is_table) =>
+            for (index, value) in array.iter().enumerate() {
+                record_sources(value, &format!("{key}.{index}"), source, sources);
+            },
+        _ => {
+            sources.insert(key.to_owned(), source.clone());
+        }
+    }
+}
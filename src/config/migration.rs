@@ -0,0 +1,113 @@
+//! Migrations of the `settings.toml` schema.
+//!
+//! Every config file carries a `version` key (files without it are version
+//! 0). When the layout of the config changes, `CONFIG_VERSION` is bumped by
+//! appending a migration to [`MIGRATIONS`], which upgrades a config table of
+//! the previous version in place.
+
+use color_eyre::eyre::{eyre, Result};
+use toml::{Table, Value};
+
+use super::ChainConfig;
+
+/// upgrades the config table from one version to the next one
+type Migration = fn(&mut Table) -> Result<()>;
+
+/// `MIGRATIONS[n]` upgrades a config of version `n` to version `n + 1`
+const MIGRATIONS: &[Migration] = &[v0_to_v1];
+
+/// version of the config schema supported by this CLI
+pub(crate) const CONFIG_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// returns the schema version of the given config table
+pub(crate) fn config_version(config: &Table) -> Result<u32> {
+    match config.get("version") {
+        None => Ok(0),
+        Some(Value::Integer(version)) => u32::try_from(*version)
+            .map_err(|_| eyre!("`version` of the config should be a positive number!")),
+        Some(_) => Err(eyre!("`version` of the config should be a number!")),
+    }
+}
+
+/// upgrades the given config table to [`CONFIG_VERSION`]
+///
+/// returns the version the config was upgraded from, or `None` if it was
+/// already up to date
+pub(crate) fn migrate(config: &mut Table) -> Result<Option<u32>> {
+    let version = config_version(config)?;
+
+    if version > CONFIG_VERSION {
+        return Err(eyre!(
+            "your config file is from a newer version of subspace-cli (config version {version}), \
+             this version only supports up to config version {CONFIG_VERSION}. Please update \
+             subspace-cli, or re-create your config with the `init` command."
+        ));
+    }
+    if version == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(config)
+            .map_err(|err| err.wrap_err(format!("couldn't migrate config from version {from}")))?;
+        config.insert("version".to_owned(), Value::Integer(from as i64 + 1));
+    }
+
+    Ok(Some(version))
+}
+
+/// - the single plot (`plot_directory` and `plot_size`) is moved into the
+///   `[[farmer.plots]]` list
+/// - `executor` and `cache_size` are moved into the `advanced` sections
+/// - chain names are normalized to their canonical spelling
+fn v0_to_v1(config: &mut Table) -> Result<()> {
+    if let Some(Value::Table(farmer)) = config.get_mut("farmer") {
+        match (farmer.remove("plot_directory"), farmer.remove("plot_size")) {
+            (Some(directory), Some(size)) => {
+                let plot = Table::from_iter([
+                    ("directory".to_owned(), directory),
+                    ("size".to_owned(), size),
+                ]);
+                match farmer.entry("plots").or_insert_with(|| Value::Array(vec![])) {
+                    Value::Array(plots) => plots.insert(0, Value::Table(plot)),
+                    _ => return Err(eyre!("`farmer.plots` should be a list!")),
+                }
+            }
+            (None, None) => (),
+            _ => return Err(eyre!("`plot_directory` and `plot_size` should be given together!")),
+        }
+
+        move_into_advanced(farmer, "cache_size")?;
+    }
+
+    if let Some(Value::Table(node)) = config.get_mut("node") {
+        move_into_advanced(node, "executor")?;
+    }
+
+    if let Some(Value::String(chain)) = config.get_mut("chain") {
+        if let Some(canonical) = ChainConfig::known()
+            .map(|chain| format!("{chain:?}"))
+            .find(|canonical| canonical.eq_ignore_ascii_case(chain))
+        {
+            *chain = canonical;
+        }
+    }
+
+    Ok(())
+}
+
+/// moves `key` of the given section into its `advanced` table
+fn move_into_advanced(section: &mut Table, key: &str) -> Result<()> {
+    let Some(value) = section.remove(key) else {
+        return Ok(());
+    };
+
+    match section.entry("advanced").or_insert_with(|| Value::Table(Table::new())) {
+        Value::Table(advanced) => {
+            // a value which is already in the `advanced` section takes precedence
+            advanced.entry(key).or_insert(value);
+            Ok(())
+        }
+        _ => Err(eyre!("`advanced` should be a table!")),
+    }
+}
@@ -0,0 +1,478 @@
+//! Preflight checks of the config.
+//!
+//! Most of the problems of a config (unwritable or overlapping directories,
+//! lack of free space, mistyped advanced options...) would otherwise only
+//! surface deep inside the SDK, after the node has started. They are collected
+//! here, and reported all together before farming.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{remove_file, File};
+use std::path::{Component, Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+use subspace_sdk::{ByteSize, Farmer, Node};
+use toml::{Table, Value};
+
+use super::{ChainConfig, Config, CustomChainConfig, PlotConfig, MIN_PLOT_SIZE};
+use crate::utils::apply_extra_options;
+
+/// errors and warnings found by the preflight checks
+#[derive(Debug, Default)]
+pub(crate) struct PreflightReport {
+    /// problems which prevent farming
+    pub(crate) errors: Vec<String>,
+    /// problems which are probably mistakes, but do not prevent farming
+    pub(crate) warnings: Vec<String>,
+}
+
+impl PreflightReport {
+    fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(message.into());
+    }
+
+    fn warning(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+
+    /// returns the warnings if there are no errors, otherwise returns an error
+    /// listing all the problems
+    pub(crate) fn into_result(self) -> Result<Vec<String>> {
+        if self.errors.is_empty() {
+            Ok(self.warnings)
+        } else {
+            Err(eyre!("{self}"))
+        }
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "found {} error(s) and {} warning(s) in the config:",
+            self.errors.len(),
+            self.warnings.len()
+        )?;
+        for error in &self.errors {
+            write!(f, "\n  - error: {error}")?;
+        }
+        for warning in &self.warnings {
+            write!(f, "\n  - warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// runs every check on the given config, including the ones inspecting the
+/// file system
+pub(crate) fn preflight(config: &Config) -> PreflightReport {
+    let mut report = PreflightReport::default();
+    check_plots(config, &mut report);
+    check_chain(config, &mut report);
+
+    let directories = directories(config);
+    check_overlapping(&directories, &mut report);
+    // the provider storage is inside the node directory by default, so it is only
+    // checked for being writable
+    let directories = directories.into_iter().chain([
+        ("provider storage".to_owned(), config.node.provider_storage_directory.clone()),
+        ("summary".to_owned(), config.farmer.summary_directory.clone()),
+    ]);
+    check_writable(directories, &mut report);
+    check_tmpfs(config, &mut report);
+    check_free_space(config, &mut report);
+
+    check_extra_options(
+        "node.advanced",
+        &Node::builder().configuration(),
+        &config.node.advanced.extra,
+        &mut report,
+    );
+    check_extra_options(
+        "farmer.advanced",
+        &Farmer::builder().configuration(),
+        &config.farmer.advanced.extra,
+        &mut report,
+    );
+
+    report
+}
+
+/// checks the values of the plots, which cannot be checked while deserializing
+pub(crate) fn check_plots(config: &Config, report: &mut PreflightReport) {
+    if config.farmer.plots.is_empty() {
+        report.error("there should be at least one plot in the config!");
+    }
+
+    let mut plot_directories = Vec::new();
+    for PlotConfig { directory, size } in &config.farmer.plots {
+        if *size < MIN_PLOT_SIZE {
+            report.error(format!(
+                "plot size of `{}` should be bigger than {MIN_PLOT_SIZE}!",
+                directory.display()
+            ));
+        }
+        if plot_directories.contains(&directory) {
+            report
+                .error(format!("plot directory `{}` is used more than once!", directory.display()));
+        } else {
+            plot_directories.push(directory);
+        }
+    }
+}
+
+/// the chain spec of a custom chain is only loaded when the node is built
+fn check_chain(config: &Config, report: &mut PreflightReport) {
+    if let ChainConfig::Custom(CustomChainConfig { custom, .. }) = &config.chain {
+        if !custom.is_file() {
+            report.error(format!("chain spec file `{}` does not exist!", custom.display()));
+        }
+    }
+}
+
+/// returns the directories used by the config, along with what they are used
+/// for
+fn directories(config: &Config) -> Vec<(String, PathBuf)> {
+    let plots = config
+        .farmer
+        .plots
+        .iter()
+        .enumerate()
+        .map(|(plot_id, plot)| (format!("plot #{plot_id}"), plot.directory.clone()));
+
+    plots
+        .chain([
+            ("node".to_owned(), config.node.directory.clone()),
+            ("cache".to_owned(), config.farmer.cache_directory.clone()),
+        ])
+        .collect()
+}
+
+/// the directories are created by the SDK if missing, so their closest existing
+/// ancestor should be writable
+fn check_writable(
+    directories: impl IntoIterator<Item = (String, PathBuf)>,
+    report: &mut PreflightReport,
+) {
+    for (usage, directory) in directories {
+        let Some(existing) = existing_ancestor(&directory) else {
+            report.error(format!("{usage} directory `{}` cannot be created!", directory.display()));
+            continue;
+        };
+        if !existing.is_dir() {
+            report.error(format!(
+                "{usage} directory `{}` cannot be created, `{}` is not a directory!",
+                directory.display(),
+                existing.display()
+            ));
+            continue;
+        }
+
+        let probe = existing.join(format!(".subspace-cli-preflight-{}", std::process::id()));
+        match File::create(&probe) {
+            Ok(_) => {
+                let _ = remove_file(&probe);
+            }
+            Err(err) => report.error(format!(
+                "{usage} directory `{}` is not writable: {err}",
+                directory.display()
+            )),
+        }
+    }
+}
+
+/// plots, node and cache should not share their directories, nor be nested into
+/// each other
+fn check_overlapping(directories: &[(String, PathBuf)], report: &mut PreflightReport) {
+    let directories = directories
+        .iter()
+        .map(|(usage, directory)| (usage, directory, normalize(directory)))
+        .collect::<Vec<_>>();
+
+    for (index, (usage, directory, normalized)) in directories.iter().enumerate() {
+        for (other_usage, other_directory, other_normalized) in &directories[index + 1..] {
+            if normalized == other_normalized {
+                // plots with the same directory are already reported by `check_plots`
+                let both_plots = usage.starts_with("plot") && other_usage.starts_with("plot");
+                if !(both_plots && directory == other_directory) {
+                    report.error(format!(
+                        "{usage} and {other_usage} cannot share the directory `{}`!",
+                        directory.display()
+                    ));
+                }
+            } else if normalized.starts_with(other_normalized) {
+                report.error(format!(
+                    "{usage} directory `{}` cannot be inside the {other_usage} directory `{}`!",
+                    directory.display(),
+                    other_directory.display()
+                ));
+            } else if other_normalized.starts_with(normalized) {
+                report.error(format!(
+                    "{other_usage} directory `{}` cannot be inside the {usage} directory `{}`!",
+                    other_directory.display(),
+                    directory.display()
+                ));
+            }
+        }
+    }
+}
+
+/// plots on a tmpfs are lost on reboot, which is rarely intended
+fn check_tmpfs(config: &Config, report: &mut PreflightReport) {
+    for (plot_id, PlotConfig { directory, .. }) in config.farmer.plots.iter().enumerate() {
+        if let Some(fs_type @ ("tmpfs" | "ramfs")) = filesystem_type(directory).as_deref() {
+            report.warning(format!(
+                "plot #{plot_id} directory `{}` is on a {fs_type}, the plot will be lost on reboot",
+                directory.display()
+            ));
+        }
+    }
+}
+
+/// the plots and the cache are allocated upfront, so there should be enough
+/// free space for them on their file systems
+fn check_free_space(config: &Config, report: &mut PreflightReport) {
+    let allocations = config
+        .farmer
+        .plots
+        .iter()
+        .enumerate()
+        .map(|(plot_id, plot)| (format!("plot #{plot_id}"), plot.directory.clone(), plot.size))
+        .chain([(
+            "cache".to_owned(),
+            config.farmer.cache_directory.clone(),
+            config.farmer.advanced.cache_size,
+        )]);
+
+    // allocations on the same file system share its free space
+    let mut filesystems: BTreeMap<PathBuf, (u64, Vec<String>)> = BTreeMap::new();
+    for (usage, directory, size) in allocations {
+        let Some(existing) = existing_ancestor(&directory) else {
+            continue;
+        };
+        // space which is already used by the directory (i.e. by an existing plot) is
+        // not allocated again
+        let required = size.as_u64().saturating_sub(directory_size(&directory));
+        let (total, usages) = filesystems.entry(filesystem_id(&existing)).or_default();
+        *total += required;
+        usages.push(usage);
+    }
+
+    for (filesystem, (required, usages)) in filesystems {
+        match fs2::available_space(&filesystem) {
+            Ok(available) if available < required => report.error(format!(
+                "not enough free space for {}: {} is required, but only {} is available at `{}`!",
+                usages.join(", "),
+                ByteSize::b(required),
+                ByteSize::b(available),
+                filesystem.display()
+            )),
+            Ok(_) => (),
+            Err(err) => report.warning(format!(
+                "couldn't check the free space at `{}`: {err}",
+                filesystem.display()
+            )),
+        }
+    }
+}
+
+/// the `extra` options of the advanced sections are applied on top of the SDK
+/// configuration, options which are not known by the SDK are probably typos
+fn check_extra_options<T: Serialize + serde::de::DeserializeOwned>(
+    section: &str,
+    configuration: &T,
+    extra: &Table,
+    report: &mut PreflightReport,
+) {
+    if extra.is_empty() {
+        return;
+    }
+    let applied = match apply_extra_options(configuration, extra.clone()) {
+        Ok(applied) => Some(applied),
+        Err(err) => {
+            report.error(format!("invalid options in `{section}`: {err:#}"));
+            None
+        }
+    };
+
+    for (key, suggestion) in unknown_options(configuration, applied.as_ref(), extra, section) {
+        match suggestion {
+            Some(suggestion) => report
+                .warning(format!("`{key}` is not a known option, did you mean `{suggestion}`?")),
+            None => report.warning(format!("`{key}` is not a known option, it may be a typo")),
+        }
+    }
+}
+
+/// returns the dotted keys of `extra` which are not accepted by the SDK
+/// configuration, along with the closest known key
+///
+/// options which are unset by default (i.e. `blocks_pruning`) are not in the
+/// serialized default configuration, but they are kept once `extra` is applied
+/// to it (`applied`), while the unknown options are dropped by the
+/// deserializer
+pub(crate) fn unknown_options<T: Serialize>(
+    configuration: &T,
+    applied: Option<&T>,
+    extra: &Table,
+    section: &str,
+) -> Vec<(String, Option<String>)> {
+    let Some(defaults) = to_table(configuration) else {
+        return vec![];
+    };
+    let unknown = unknown_keys(&defaults, extra, section);
+    match applied.and_then(to_table) {
+        Some(applied) => {
+            let dropped = unknown_keys(&applied, extra, section)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>();
+            unknown.into_iter().filter(|(key, _)| dropped.contains(key)).collect()
+        }
+        None => unknown,
+    }
+}
+
+/// serializes the given configuration into a table, through JSON since TOML
+/// does not support every enum (i.e. `Some(256)`), without its unset options
+fn to_table<T: Serialize>(configuration: &T) -> Option<Table> {
+    fn remove_nulls(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(object) => {
+                object.retain(|_, value| !value.is_null());
+                object.values_mut().for_each(remove_nulls);
+            }
+            serde_json::Value::Array(array) => array.iter_mut().for_each(remove_nulls),
+            _ => (),
+        }
+    }
+
+    let mut json = serde_json::to_value(configuration).ok()?;
+    remove_nulls(&mut json);
+    match Value::try_from(json) {
+        Ok(Value::Table(table)) => Some(table),
+        _ => None,
+    }
+}
+
+/// returns the dotted keys of `extra` which are not in `known`, along with the
+/// closest known key
+pub(crate) fn unknown_keys(
+    known: &Table,
+    extra: &Table,
+    prefix: &str,
+) -> Vec<(String, Option<String>)> {
+    let mut unknown = vec![];
+    for (key, value) in extra {
+        match (known.get(key), value) {
+            (Some(Value::Table(known)), Value::Table(extra)) =>
+                unknown.extend(unknown_keys(known, extra, &format!("{prefix}.{key}"))),
+            (Some(_), _) => (),
+            (None, _) => {
+                let suggestion = known
+                    .keys()
+                    .map(|candidate| (strsim::jaro_winkler(key, candidate), candidate))
+                    .filter(|(similarity, _)| *similarity > 0.8)
+                    .max_by(|(a, _), (b, _)| a.total_cmp(b))
+                    .map(|(_, candidate)| candidate.clone());
+                unknown.push((format!("{prefix}.{key}"), suggestion));
+            }
+        }
+    }
+    unknown
+}
+
+/// returns the absolute form of the path, resolving the symlinks of its
+/// existing part
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let absolute = match std::env::current_dir() {
+        Ok(current_dir) if path.is_relative() => current_dir.join(path),
+        _ => path.to_owned(),
+    };
+    let Some(existing) = existing_ancestor(&absolute) else {
+        return absolute;
+    };
+    let rest = absolute.strip_prefix(&existing).map(Path::to_owned).unwrap_or_default();
+    let mut normalized = existing.canonicalize().unwrap_or(existing);
+    for component in rest.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => (),
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// returns the path itself if it exists, otherwise its closest existing
+/// ancestor
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    let absolute = match std::env::current_dir() {
+        Ok(current_dir) if path.is_relative() => current_dir.join(path),
+        _ => path.to_owned(),
+    };
+    absolute.ancestors().find(|ancestor| ancestor.exists()).map(Path::to_owned)
+}
+
+/// returns the total size of the files in the directory
+fn directory_size(directory: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => directory_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// returns a path identifying the file system of the given existing path
+///
+/// on linux, this is the mount point of the file system, elsewhere the path
+/// itself
+fn filesystem_id(path: &Path) -> PathBuf {
+    #[cfg(target_os = "linux")]
+    if let Some((mount_point, _)) = mount_point(path) {
+        return mount_point;
+    }
+
+    path.to_owned()
+}
+
+/// returns the type of the file system the path is on (i.e. `ext4`, `tmpfs`)
+fn filesystem_type(path: &Path) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    return mount_point(path).map(|(_, fs_type)| fs_type);
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// returns the mount point containing the path, and the type of its file
+/// system
+#[cfg(target_os = "linux")]
+fn mount_point(path: &Path) -> Option<(PathBuf, String)> {
+    let path = normalize(path);
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            // spaces in mount points are escaped as `\040`
+            let mount_point = PathBuf::from(fields.next()?.replace("\\040", " "));
+            let fs_type = fields.next()?.to_owned();
+            path.starts_with(&mount_point).then_some((mount_point, fs_type))
+        })
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+}
@@ -0,0 +1,215 @@
+//! Control socket of the `farm` command.
+//!
+//! `farm` listens on a Unix domain socket in the data directory of the
+//! profile, so that the other commands talk to the running farm: `info` shows
+//! its live status, and `stop`, `pause-plotting` and `set-log-level` act on it.
+//! A request is a line of JSON (i.e. `{"command":"stop"}`), answered with a
+//! line of JSON, after which the connection is closed.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(unix)]
+use color_eyre::eyre::Context;
+use color_eyre::eyre::{eyre, Result};
+use futures::Future;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::instrument;
+
+use crate::status::{Status, StatusReport};
+use crate::utils::{control_socket_getter, set_log_filter};
+
+/// answer to `pause-plotting`: the farmer of the SDK plots in the background,
+/// and has no way to pause it (dropping the subscription to the plotting
+/// progress would only hide it)
+pub(crate) const PAUSE_PLOTTING_UNSUPPORTED: &str =
+    "Pausing the plotting is not supported by this farmer, the plotting goes on";
+
+/// request to the running farm
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub(crate) enum ControlRequest {
+    /// returns the live status of the farm
+    Status,
+    /// shuts the farm down gracefully, the same way as `ctrl+c`
+    Stop,
+    /// pauses the initial plotting, answered with
+    /// [`PAUSE_PLOTTING_UNSUPPORTED`] as long as the farmer cannot pause it
+    PausePlotting,
+    /// replaces the filter of the logs (i.e. `debug`, or
+    /// `info,subspace_cli=debug`)
+    SetLogLevel { filter: String },
+}
+
+/// response of the running farm
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ControlResponse {
+    Status(Box<StatusReport>),
+    Done,
+    Error(String),
+}
+
+/// what the requests act on, cheap to clone
+#[derive(Debug, Clone)]
+pub(crate) struct Control {
+    status: Status,
+    stop: Arc<Notify>,
+}
+
+impl Control {
+    /// controls the farm with the given status, and notifies `stop` once a
+    /// stop is requested
+    pub(crate) fn new(status: Status, stop: Arc<Notify>) -> Control {
+        Control { status, stop }
+    }
+
+    /// acts on the given request
+    #[instrument]
+    pub(crate) fn handle(&self, request: ControlRequest) -> ControlResponse {
+        match request {
+            ControlRequest::Status => ControlResponse::Status(Box::new(self.status.report())),
+            ControlRequest::Stop => {
+                self.stop.notify_one();
+                ControlResponse::Done
+            }
+            ControlRequest::PausePlotting =>
+                ControlResponse::Error(PAUSE_PLOTTING_UNSUPPORTED.to_owned()),
+            ControlRequest::SetLogLevel { filter } => match set_log_filter(&filter) {
+                Ok(()) => ControlResponse::Done,
+                Err(err) => ControlResponse::Error(format!("{err:#}")),
+            },
+        }
+    }
+}
+
+/// removes the control socket once it is dropped
+#[derive(Debug)]
+pub(crate) struct ControlSocket {
+    path: PathBuf,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// listens on the control socket at the given path, and returns the server
+/// answering the requests
+///
+/// the socket is only removed once the returned [`ControlSocket`] is dropped
+#[cfg(unix)]
+#[instrument]
+pub(crate) fn listen(
+    path: &Path,
+    control: Control,
+) -> Result<(ControlSocket, impl Future<Output = Result<()>>)> {
+    let path = path.to_owned();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("couldn't create the data directory")?;
+    }
+    // the instance lock is held, so the socket is left over from a crashed farm
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path)
+        .wrap_err_with(|| format!("couldn't listen on `{}`", path.display()))?;
+    let socket = ControlSocket { path };
+
+    let server = async move {
+        loop {
+            let (stream, _) =
+                listener.accept().await.context("couldn't accept a control connection")?;
+            let control = control.clone();
+            tokio::spawn(async move {
+                if let Err(err) = answer(stream, &control).await {
+                    tracing::warn!("control connection failed: {err:?}");
+                }
+            });
+        }
+    };
+    Ok((socket, server))
+}
+
+/// the control socket is only available on Unix
+#[cfg(not(unix))]
+pub(crate) fn listen(
+    _path: &Path,
+    _control: Control,
+) -> Result<(ControlSocket, impl Future<Output = Result<()>>)> {
+    Err::<(ControlSocket, futures::future::Pending<Result<()>>), _>(eyre!(
+        "the control socket is only available on Unix"
+    ))
+}
+
+/// answers the request of the given connection
+#[cfg(unix)]
+async fn answer(stream: tokio::net::UnixStream, control: &Control) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await.context("couldn't read the request")?;
+    let response = match serde_json::from_str(&line) {
+        Ok(request) => control.handle(request),
+        Err(err) => ControlResponse::Error(format!("invalid request: {err}")),
+    };
+
+    let mut response = serde_json::to_string(&response).context("couldn't serialize response")?;
+    response.push('\n');
+    writer.write_all(response.as_bytes()).await.context("couldn't write the response")
+}
+
+/// sends the given request to the farm listening on the control socket at the
+/// given path, `None` if there is no running farm
+#[cfg(unix)]
+#[instrument]
+pub(crate) async fn send_request(
+    path: &Path,
+    request: &ControlRequest,
+) -> Result<Option<ControlResponse>> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = match tokio::net::UnixStream::connect(path).await {
+        Ok(stream) => stream,
+        // a missing or a left over socket, from a crashed farm
+        Err(err)
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+            ) =>
+            return Ok(None),
+        Err(err) =>
+            return Err(err).wrap_err_with(|| format!("couldn't connect to `{}`", path.display())),
+    };
+
+    let (reader, mut writer) = stream.into_split();
+    let mut request = serde_json::to_string(request).context("couldn't serialize request")?;
+    request.push('\n');
+    writer.write_all(request.as_bytes()).await.context("couldn't send the request")?;
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await.context("couldn't read the response")?;
+    serde_json::from_str(&line).context("invalid response from the farm").map(Some)
+}
+
+/// the control socket is only available on Unix, so there is never a farm to
+/// talk to
+#[cfg(not(unix))]
+pub(crate) async fn send_request(
+    _path: &Path,
+    _request: &ControlRequest,
+) -> Result<Option<ControlResponse>> {
+    Ok(None)
+}
+
+/// sends the given request to the farm running for the profile, which should
+/// act on it
+pub(crate) async fn act(request: ControlRequest) -> Result<()> {
+    match send_request(&control_socket_getter(), &request).await? {
+        Some(ControlResponse::Done) => Ok(()),
+        Some(ControlResponse::Error(err)) => Err(eyre!(err)),
+        Some(response) => Err(eyre!("unexpected response from the farm: {response:?}")),
+        None => Err(eyre!("There is no active farmer instance...")),
+    }
+}
@@ -0,0 +1,51 @@
+//! Single data root of the CLI.
+//!
+//! By default, the files of the CLI are spread over the directories of the OS
+//! (config, cache, data and logs). With `--data-dir` (or `SUBSPACE_CLI_HOME`),
+//! they all live under a single root instead, so that a farm can be kept on an
+//! external drive and moved between machines. Relative paths in the config are
+//! then resolved against the root of the profile, see [`profile_home`].
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use color_eyre::eyre::{Context, Result};
+
+use crate::profile::profile;
+
+/// data directory supplied on the command line
+static DATA_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// stores the data directory supplied on the command line, should be called
+/// once on startup
+pub(crate) fn set_data_dir(data_dir: Option<PathBuf>) -> Result<()> {
+    // relative paths in the config are resolved against the data directory, so it
+    // should not depend on the current directory
+    let data_dir = match data_dir {
+        Some(data_dir) if data_dir.is_relative() => Some(
+            std::env::current_dir()
+                .context("couldn't resolve the relative data directory")?
+                .join(data_dir),
+        ),
+        data_dir => data_dir,
+    };
+    DATA_DIR.set(data_dir).expect("data directory is only set once on startup");
+    Ok(())
+}
+
+/// returns the data directory supplied on the command line, if any
+pub(crate) fn data_dir() -> Option<&'static Path> {
+    DATA_DIR.get_or_init(|| None).as_deref()
+}
+
+/// returns the data directory if it is supplied, otherwise the given OS
+/// directory
+pub(crate) fn data_dir_or(os_dir: impl FnOnce() -> PathBuf) -> PathBuf {
+    data_dir().map_or_else(os_dir, Path::to_owned)
+}
+
+/// returns the directory of the selected profile in the data directory, which
+/// holds all of its files
+pub(crate) fn profile_home() -> Option<PathBuf> {
+    data_dir().map(|data_dir| profile().namespace(data_dir.to_owned()))
+}
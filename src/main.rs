@@ -7,13 +7,21 @@
 
 mod commands;
 mod config;
+mod control;
+mod home;
+mod metrics;
+mod output;
+mod profile;
+mod status;
 mod summary;
+mod systemd;
 mod utils;
 
 #[cfg(test)]
 mod tests;
 
 use std::io::{self, Write};
+use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 use color_eyre::eyre::{Context, Report};
@@ -26,11 +34,22 @@ use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use tracing::instrument;
 
+use crate::commands::config::{config, ConfigCommand};
+use crate::commands::export::{export, ExportCommand};
 use crate::commands::farm::farm;
-use crate::commands::info::info;
-use crate::commands::init::init;
+use crate::commands::info::{info, InfoArgs};
+use crate::commands::init::{init, InitArgs};
+use crate::commands::profiles::{profiles, ProfilesCommand};
+use crate::commands::service::{service, ServiceCommand};
 use crate::commands::wipe::wipe_config;
-use crate::utils::{get_user_input, open_log_dir, support_message, yes_or_no_parser};
+use crate::config::layers::set_cli_overrides;
+use crate::control::{act, ControlRequest};
+use crate::home::set_data_dir;
+use crate::output::OutputFormat;
+use crate::profile::{profile_name_parser, set_profile, Profile, DEFAULT_PROFILE};
+use crate::utils::{
+    get_user_input, key_value_parser, open_log_dir, support_message, yes_or_no_parser,
+};
 
 #[cfg(all(
     target_arch = "x86_64",
@@ -49,19 +68,39 @@ static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// overrides a config value, takes precedence over the config file and the
+    /// `SUBSPACE_CLI__*` environment variables (i.e. `--set
+    /// farmer.plots.0.size=2TB`)
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = key_value_parser, global = true)]
+    overrides: Vec<(String, String)>,
+    /// name of the profile to use, each profile has its own config, summary,
+    /// node, plots, cache and logs, so several farms can run on one host
+    #[arg(long, env = "SUBSPACE_CLI_PROFILE", value_parser = profile_name_parser, global = true)]
+    profile: Option<String>,
+    /// path of the config file to use, instead of the one of the profile
+    #[arg(long = "config", value_name = "PATH", env = "SUBSPACE_CLI_CONFIG", global = true)]
+    config_path: Option<PathBuf>,
+    /// keeps all the files of the CLI (config, summary, logs, node, plots and
+    /// cache) under the given directory, instead of the directories of the OS
+    #[arg(long, value_name = "PATH", env = "SUBSPACE_CLI_HOME", global = true)]
+    data_dir: Option<PathBuf>,
 }
 
 /// Available commands for the CLI
 #[derive(Debug, Subcommand, EnumIter)]
 enum Commands {
-    #[command(about = "initializes the config file required for the farming")]
-    Init,
+    #[command(about = "initializes the config file required for the farming", long_about = None)]
+    Init(Box<InitArgs>),
     #[command(about = "starting the farming process (along with node in the background)")]
     Farm {
         #[arg(short, long, action)]
         verbose: bool,
         #[arg(short, long, action)]
         executor: bool,
+        /// prints progress bars (`text`), or a line of JSON per event (`json`,
+        /// the default when stdout is not a terminal)
+        #[arg(long, value_enum, conflicts_with = "verbose")]
+        output: Option<OutputFormat>,
     },
     #[command(about = "wipes the node and farm instance (along with your plots)")]
     Wipe {
@@ -69,33 +108,98 @@ enum Commands {
         farmer: bool,
         #[arg(long, action)]
         node: bool,
+        /// wipes only the plot in the given directory (can be repeated)
+        #[arg(long, value_name = "PLOT_DIRECTORY")]
+        plot: Vec<PathBuf>,
     },
     #[command(about = "displays info about the farmer instance (i.e. total amount of rewards, \
                        and status of initial plotting)")]
-    Info,
+    Info(InfoArgs),
     OpenLogs,
+    #[command(about = "shows, edits and validates the config file")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    #[command(about = "exports the rewards of the farmer (i.e. for accounting)")]
+    Export {
+        #[command(subcommand)]
+        command: ExportCommand,
+    },
+    #[command(about = "lists the profiles of this host")]
+    Profiles {
+        #[command(subcommand)]
+        command: ProfilesCommand,
+    },
+    #[command(about = "stops the running farm gracefully")]
+    Stop,
+    #[command(about = "pauses the initial plotting of the running farm")]
+    #[strum(disabled)]
+    PausePlotting,
+    #[command(about = "changes the log level of the running farm")]
+    #[strum(disabled)]
+    SetLogLevel {
+        /// level of the logs (i.e. `debug`), or a filter of them (i.e.
+        /// `info,subspace_cli=debug`)
+        filter: String,
+    },
+    #[command(about = "runs the farm as a systemd service")]
+    #[strum(disabled)]
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommand,
+    },
 }
 
 #[tokio::main]
 #[instrument]
 async fn main() -> Result<(), Report> {
     let args = Cli::parse();
+    set_cli_overrides(args.overrides);
+    set_data_dir(args.data_dir)?;
+    set_profile(
+        Profile::named(args.profile.as_deref().unwrap_or(DEFAULT_PROFILE))
+            .with_config_path(args.config_path),
+    );
     match args.command {
-        Some(Commands::Info) => {
-            info().await.suggestion(support_message())?;
+        Some(Commands::Info(args)) => {
+            info(args).await.suggestion(support_message())?;
         }
-        Some(Commands::Init) => {
-            init().suggestion(support_message())?;
+        Some(Commands::Init(args)) => {
+            init(*args).suggestion(support_message())?;
         }
-        Some(Commands::Farm { verbose, executor }) => {
-            farm(verbose, executor).await.suggestion(support_message())?;
+        Some(Commands::Farm { verbose, executor, output }) => {
+            farm(verbose, executor, output).await.suggestion(support_message())?;
         }
-        Some(Commands::Wipe { farmer, node }) => {
-            wipe_config(farmer, node).await.suggestion(support_message())?;
+        Some(Commands::Wipe { farmer, node, plot }) => {
+            wipe_config(farmer, node, plot).await.suggestion(support_message())?;
         }
         Some(Commands::OpenLogs) => {
             open_log_dir().suggestion(support_message())?;
         }
+        Some(Commands::Config { command }) => {
+            config(command).suggestion(support_message())?;
+        }
+        Some(Commands::Export { command }) => {
+            export(command).await.suggestion(support_message())?;
+        }
+        Some(Commands::Profiles { command }) => {
+            profiles(command).suggestion(support_message())?;
+        }
+        Some(Commands::Stop) => {
+            stop().await.suggestion(support_message())?;
+        }
+        Some(Commands::PausePlotting) => {
+            act(ControlRequest::PausePlotting).await.suggestion(support_message())?;
+            println!("Paused the initial plotting!");
+        }
+        Some(Commands::SetLogLevel { filter }) => {
+            act(ControlRequest::SetLogLevel { filter }).await.suggestion(support_message())?;
+            println!("Changed the log level of the farm!");
+        }
+        Some(Commands::Service { command }) => {
+            service(command).suggestion(support_message())?;
+        }
         None => arrow_key_mode().await.suggestion(support_message())?,
     }
 
@@ -158,7 +262,7 @@ async fn arrow_key_mode() -> Result<(), Report> {
 
     match selected {
         0 => {
-            init().suggestion(support_message())?;
+            init(InitArgs::default()).suggestion(support_message())?;
         }
         1 => {
             let prompt = "Do you want to initialize farmer in verbose mode? [y/n]: ";
@@ -169,25 +273,44 @@ async fn arrow_key_mode() -> Result<(), Report> {
             let executor =
                 get_user_input(prompt, None, yes_or_no_parser).context("prompt failed")?;
 
-            farm(verbose, executor).await.suggestion(support_message())?;
+            farm(verbose, executor, None).await.suggestion(support_message())?;
         }
         2 => {
-            wipe_config(false, false).await.suggestion(support_message())?;
+            wipe_config(false, false, vec![]).await.suggestion(support_message())?;
         }
         3 => {
-            info().await.suggestion(support_message())?;
+            info(InfoArgs::default()).await.suggestion(support_message())?;
         }
         4 => {
             open_log_dir().suggestion(support_message())?;
         }
+        5 => {
+            config(ConfigCommand::Show).suggestion(support_message())?;
+        }
+        6 => {
+            export(ExportCommand::default()).await.suggestion(support_message())?;
+        }
+        7 => {
+            profiles(ProfilesCommand::List).suggestion(support_message())?;
+        }
+        8 => {
+            stop().await.suggestion(support_message())?;
+        }
         _ => {
-            unreachable!("this number must stay in [0-4]")
+            unreachable!("this number must stay in [0-8]")
         }
     }
 
     Ok(())
 }
 
+/// asks the running farm to stop
+async fn stop() -> Result<(), Report> {
+    act(ControlRequest::Stop).await?;
+    println!("The farm is shutting down gracefully...");
+    Ok(())
+}
+
 // Helper function to print options to the terminal
 fn print_options(
     stdout: &mut io::Stdout,
@@ -219,11 +342,18 @@ fn print_options(
 impl std::fmt::Display for Commands {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match *self {
-            Commands::Farm { verbose: _, executor: _ } => write!(f, "farm"),
-            Commands::Wipe { farmer: _, node: _ } => write!(f, "wipe"),
-            Commands::Info => write!(f, "info"),
-            Commands::Init => write!(f, "init"),
+            Commands::Farm { .. } => write!(f, "farm"),
+            Commands::Wipe { .. } => write!(f, "wipe"),
+            Commands::Info(_) => write!(f, "info"),
+            Commands::Init(_) => write!(f, "init"),
             Commands::OpenLogs => write!(f, "open logs directory"),
+            Commands::Config { .. } => write!(f, "show config"),
+            Commands::Export { .. } => write!(f, "export rewards"),
+            Commands::Profiles { .. } => write!(f, "list profiles"),
+            Commands::Stop => write!(f, "stop farming"),
+            Commands::PausePlotting => write!(f, "pause plotting"),
+            Commands::SetLogLevel { .. } => write!(f, "set log level"),
+            Commands::Service { .. } => write!(f, "install service"),
         }
     }
 }
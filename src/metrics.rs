@@ -0,0 +1,156 @@
+//! Prometheus metrics of the `farm` command.
+//!
+//! The metrics are only served if the config has a `[metrics]` section, at
+//! `http://<address>/metrics` in the Prometheus text format, whatever the
+//! output of `farm` is (including `--verbose`). They are updated along with the
+//! progress (drawn or not) and the summary.
+
+use std::net::SocketAddr;
+
+use color_eyre::eyre::{Context, Result};
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use prometheus::{Encoder, Gauge, GaugeVec, IntGauge, Opts, Registry, TextEncoder};
+use subspace_sdk::node::BlockNumber;
+use tracing::instrument;
+
+use crate::summary::Summary;
+use crate::utils::serve_http;
+
+/// prefix of the names of the metrics
+const NAMESPACE: &str = "subspace_cli";
+
+/// metrics of the farm, cheap to clone
+#[derive(Debug, Clone)]
+pub(crate) struct Metrics {
+    registry: Registry,
+    sync_position: IntGauge,
+    sync_target: IntGauge,
+    plotting_progress: GaugeVec,
+    // the totals are gauges rather than counters, as a reorg reverts them
+    rewards: Gauge,
+    votes: IntGauge,
+    authored_blocks: IntGauge,
+    last_processed_block: IntGauge,
+    finalized_block: IntGauge,
+    scanner_lag: IntGauge,
+}
+
+impl Metrics {
+    /// registers the metrics of the farm
+    pub(crate) fn new() -> Result<Metrics> {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_owned()), None)
+            .context("couldn't create the metrics registry")?;
+        let int_gauge = |name: &str, help: &str| -> Result<IntGauge> {
+            let gauge = IntGauge::new(name, help).context("invalid metric")?;
+            registry.register(Box::new(gauge.clone())).context("couldn't register the metric")?;
+            Ok(gauge)
+        };
+
+        let plotting_progress = GaugeVec::new(
+            Opts::new("plotting_progress_ratio", "progress of the initial plotting, per plot"),
+            &["plot"],
+        )
+        .context("invalid metric")?;
+        registry
+            .register(Box::new(plotting_progress.clone()))
+            .context("couldn't register the metric")?;
+        let rewards = Gauge::new("rewards", "rewards of the farmer, in the token of the chain")
+            .context("invalid metric")?;
+        registry.register(Box::new(rewards.clone())).context("couldn't register the metric")?;
+
+        Ok(Metrics {
+            sync_position: int_gauge("sync_position_blocks", "block the node is synced to")?,
+            sync_target: int_gauge("sync_target_blocks", "block the node is syncing to")?,
+            plotting_progress,
+            rewards,
+            votes: int_gauge("votes", "votes of the farmer")?,
+            authored_blocks: int_gauge("authored_blocks", "blocks authored by the farmer")?,
+            last_processed_block: int_gauge(
+                "last_processed_block",
+                "last block whose rewards are counted",
+            )?,
+            finalized_block: int_gauge("finalized_block", "last finalized block of the chain")?,
+            scanner_lag: int_gauge(
+                "scanner_lag_blocks",
+                "finalized blocks whose rewards are not counted yet",
+            )?,
+            registry,
+        })
+    }
+
+    /// records the syncing progress of the node
+    pub(crate) fn set_syncing(&self, position: BlockNumber, target: BlockNumber) {
+        self.sync_position.set(position.into());
+        self.sync_target.set(target.into());
+    }
+
+    /// records the initial plotting progress of the given plot
+    pub(crate) fn set_plotting(&self, plot_id: usize, plotted: u64, allocated: u64) {
+        let ratio = if allocated == 0 { 1.0 } else { plotted as f64 / allocated as f64 };
+        self.plotting_progress.with_label_values(&[&plot_id.to_string()]).set(ratio);
+    }
+
+    /// records the totals of the given summary
+    pub(crate) fn set_summary(&self, summary: &Summary) {
+        let token = summary.token.clone().unwrap_or_default();
+        // precision is lost, but the exact amount is in the summary
+        self.rewards.set(summary.total_rewards.0 as f64 / 10f64.powi(token.decimals.into()));
+        self.votes.set(summary.vote_count.try_into().unwrap_or(i64::MAX));
+        self.authored_blocks.set(summary.authored_count.try_into().unwrap_or(i64::MAX));
+        // the summary knows the next block to process
+        self.last_processed_block.set(summary.last_processed_block_num.saturating_sub(1).into());
+        self.update_scanner_lag();
+    }
+
+    /// records the last finalized block of the chain
+    pub(crate) fn set_finalized_block(&self, block_number: BlockNumber) {
+        self.finalized_block.set(block_number.into());
+        self.update_scanner_lag();
+    }
+
+    /// updates the lag of the scanner from the finalized and the last processed
+    /// blocks
+    fn update_scanner_lag(&self) {
+        let lag = self.finalized_block.get() - self.last_processed_block.get();
+        self.scanner_lag.set(lag.max(0));
+    }
+
+    /// returns the metrics in the Prometheus text format
+    pub(crate) fn encode(&self) -> Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .context("couldn't encode the metrics")?;
+        String::from_utf8(buffer).context("metrics are not UTF-8")
+    }
+
+    /// responds to a request of the metrics endpoint
+    fn respond(&self, request: &Request<Body>) -> Response<Body> {
+        let response = match (request.method(), request.uri().path()) {
+            (&Method::GET, "/metrics") => match self.encode() {
+                Ok(metrics) => Response::builder()
+                    .header(CONTENT_TYPE, TextEncoder::new().format_type())
+                    .body(Body::from(metrics)),
+                Err(err) => {
+                    tracing::error!("{err:?}");
+                    Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .body(Body::from(err.to_string()))
+                }
+            },
+            _ => Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()),
+        };
+        response.unwrap_or_else(|_| Response::new(Body::empty()))
+    }
+}
+
+/// binds to the given address, and returns the server of the metrics
+#[instrument]
+pub(crate) fn serve_metrics(
+    address: SocketAddr,
+    metrics: Metrics,
+) -> Result<impl Future<Output = Result<()>>> {
+    serve_http(address, "metrics", move |request| metrics.respond(request))
+}
@@ -0,0 +1,152 @@
+//! Output of the `farm` command, for humans or for machines.
+//!
+//! By default, `farm` draws progress bars and a status line, which turn the
+//! logs of a service (i.e. under systemd or Docker) into garbage. With
+//! `--output json` (the default when stdout is not a terminal), every event of
+//! the farm is a line of JSON on stdout instead, and the messages for humans go
+//! to stderr.
+
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use crossterm::tty::IsTty;
+use serde::Serialize;
+use subspace_sdk::node::BlockNumber;
+
+use crate::summary::ledger::{LedgerRecord, RewardKind};
+use crate::summary::Token;
+use crate::utils::format_amount;
+
+/// output format selected for `farm`
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// format of the output of `farm`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    /// progress bars and messages
+    #[default]
+    Text,
+    /// a line of JSON per event
+    Json,
+}
+
+impl OutputFormat {
+    /// returns the given format, or the one suiting stdout if none is given
+    ///
+    /// the logs of `--verbose` are printed to stdout, so they are only ever
+    /// mixed with text
+    pub(crate) fn select(format: Option<OutputFormat>, is_verbose: bool) -> OutputFormat {
+        match format {
+            Some(format) => format,
+            None if is_verbose || std::io::stdout().is_tty() => OutputFormat::Text,
+            None => OutputFormat::Json,
+        }
+    }
+}
+
+/// event of the farm, see the [module docs](self)
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum FarmEvent {
+    SyncProgress {
+        position: BlockNumber,
+        target: BlockNumber,
+    },
+    PlottingProgress {
+        plot: usize,
+        plotted_bytes: u64,
+        allocated_bytes: u64,
+    },
+    PlottingFinished {
+        plot: usize,
+    },
+    Reward {
+        block_number: BlockNumber,
+        block_hash: String,
+        /// `block_reward` or `vote_reward`
+        kind: String,
+        /// in the smallest unit of the token, as a string since it does not fit
+        /// into the numbers of JSON
+        amount: String,
+        /// in the token, i.e. `1.5`
+        amount_tokens: String,
+        symbol: String,
+    },
+    Vote {
+        block_number: BlockNumber,
+        block_hash: String,
+    },
+    AuthoredBlock {
+        block_number: BlockNumber,
+    },
+    Shutdown,
+}
+
+impl FarmEvent {
+    /// returns the event of the given record of the ledger
+    pub(crate) fn from_record(
+        LedgerRecord { block_number, block_hash, kind, amount, .. }: &LedgerRecord,
+        token: &Token,
+    ) -> FarmEvent {
+        match kind {
+            RewardKind::Vote =>
+                FarmEvent::Vote { block_number: *block_number, block_hash: block_hash.clone() },
+            RewardKind::BlockReward | RewardKind::VoteReward => FarmEvent::Reward {
+                block_number: *block_number,
+                block_hash: block_hash.clone(),
+                kind: kind.to_string(),
+                amount: amount.to_string(),
+                amount_tokens: format_amount(*amount, token.decimals),
+                symbol: token.symbol.clone(),
+            },
+        }
+    }
+}
+
+/// a line of the JSON output
+#[derive(Serialize)]
+struct EventLine<'a> {
+    /// unix timestamp of the event, in seconds
+    time: u64,
+    #[serde(flatten)]
+    event: &'a FarmEvent,
+}
+
+/// stores the output format selected for `farm`, should be called once on
+/// startup
+pub(crate) fn set_output_format(format: OutputFormat) {
+    let _ = OUTPUT_FORMAT.set(format);
+}
+
+/// returns the output format selected for `farm`
+pub(crate) fn output_format() -> OutputFormat {
+    *OUTPUT_FORMAT.get_or_init(OutputFormat::default)
+}
+
+/// prints the given event as a line of JSON, if the output is JSON
+pub(crate) fn emit(event: FarmEvent) {
+    if output_format() != OutputFormat::Json {
+        return;
+    }
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    match serde_json::to_string(&EventLine { time, event: &event }) {
+        Ok(line) => {
+            // the lock keeps the lines of concurrent events apart
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{line}");
+            let _ = stdout.flush();
+        }
+        Err(err) => tracing::error!("couldn't serialize the event {event:?}: {err}"),
+    }
+}
+
+/// prints the given message for humans, to stderr if the output is JSON
+pub(crate) fn print_line(message: impl Display) {
+    match output_format() {
+        OutputFormat::Text => println!("{message}"),
+        OutputFormat::Json => eprintln!("{message}"),
+    }
+}
@@ -0,0 +1,186 @@
+//! Named profiles, which allow running several independent farms on a single
+//! host.
+//!
+//! The default profile uses the usual locations of the CLI. A named profile
+//! (`--profile <name>`) keeps its config, summary, logs, node, plots and cache
+//! in a `profiles/<name>` sub-directory of those locations, and takes its own
+//! instance lock, so that it does not interfere with the other profiles.
+//!
+//! A config file supplied with `--config` is a farm of its own as well: it
+//! takes its own instance lock, logs and control socket, in a
+//! `configs/<hash of its path>` sub-directory of those of the profile. The
+//! other locations (i.e. the node and the summary) come from the config file.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use color_eyre::eyre::{eyre, Context, Result};
+
+use crate::home::data_dir;
+
+/// name of the profile used when `--profile` is not supplied
+pub(crate) const DEFAULT_PROFILE: &str = "default";
+
+/// directory of the named profiles, in every location of the CLI
+const PROFILES_DIR: &str = "profiles";
+
+/// directory of the farms of the config files supplied with `--config`
+const CONFIGS_DIR: &str = "configs";
+
+/// allows us to detect multiple instances of the farmer for the same profile
+const INSTANCE_LOCK: &str = ".subspaceFarmer";
+
+/// profile selected on the command line
+static PROFILE: OnceLock<Profile> = OnceLock::new();
+
+/// a profile of the CLI, see the [module docs](self)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Profile {
+    /// `None` for the default profile
+    name: Option<String>,
+    /// config file supplied with `--config`, used instead of the one of the
+    /// profile
+    config_path: Option<PathBuf>,
+}
+
+impl Profile {
+    /// returns the profile with the given name
+    pub(crate) fn named(name: &str) -> Profile {
+        Profile { name: (name != DEFAULT_PROFILE).then(|| name.to_owned()), config_path: None }
+    }
+
+    /// uses the given config file instead of the one of the profile
+    pub(crate) fn with_config_path(self, config_path: Option<PathBuf>) -> Profile {
+        Profile { config_path, ..self }
+    }
+
+    /// returns the name of the profile
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    /// returns the config file supplied with `--config`, if any
+    pub(crate) fn config_path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+
+    /// returns the given location of the CLI, namespaced for this profile
+    pub(crate) fn namespace(&self, dir: PathBuf) -> PathBuf {
+        match &self.name {
+            Some(name) => dir.join(PROFILES_DIR).join(name),
+            None => dir,
+        }
+    }
+
+    /// returns the given location of the farm, namespaced for the config file
+    /// supplied with `--config`, so that the farms of different config files
+    /// do not share it
+    pub(crate) fn farm_namespace(&self, dir: PathBuf) -> PathBuf {
+        match self.config_hash() {
+            Some(config_hash) => dir.join(CONFIGS_DIR).join(config_hash),
+            None => dir,
+        }
+    }
+
+    /// returns the hash of the config file supplied with `--config`, if any
+    fn config_hash(&self) -> Option<String> {
+        self.config_path.as_ref().map(|config_path| {
+            // the same file is the same farm, however its path is spelled
+            let config_path = config_path.canonicalize().unwrap_or_else(|_| config_path.clone());
+            let mut hasher = DefaultHasher::new();
+            config_path.hash(&mut hasher);
+            format!("{:x}", hasher.finish())
+        })
+    }
+
+    /// returns the name of the instance lock of this profile
+    ///
+    /// farms in different data directories, or of different config files, are
+    /// independent, so they are a part of the lock as well
+    pub(crate) fn instance_lock(&self) -> String {
+        let mut lock = match &self.name {
+            Some(name) => format!("{INSTANCE_LOCK}-{name}"),
+            None => INSTANCE_LOCK.to_owned(),
+        };
+        if let Some(data_dir) = data_dir() {
+            let mut hasher = DefaultHasher::new();
+            data_dir.hash(&mut hasher);
+            lock.push_str(&format!("-{:x}", hasher.finish()));
+        }
+        if let Some(config_hash) = self.config_hash() {
+            lock.push_str(&format!("-{config_hash}"));
+        }
+        lock
+    }
+
+    /// returns the arguments selecting this profile on the command line
+    pub(crate) fn args(&self) -> String {
+        let mut args = String::new();
+        if let Some(name) = &self.name {
+            args.push_str(&format!(" --profile {name}"));
+        }
+        if let Some(config_path) = &self.config_path {
+            args.push_str(&format!(" --config {config_path:?}"));
+        }
+        args
+    }
+}
+
+/// stores the profile selected on the command line, should be called once on
+/// startup
+pub(crate) fn set_profile(profile: Profile) {
+    PROFILE.set(profile).expect("profile is only set once on startup");
+}
+
+/// returns the profile selected on the command line
+pub(crate) fn profile() -> &'static Profile {
+    PROFILE.get_or_init(Profile::default)
+}
+
+/// profile names are used in paths and lock names, so only letters, digits,
+/// `-` and `_` are allowed
+pub(crate) fn profile_name_parser(name: &str) -> Result<String> {
+    if name.is_empty() {
+        return Err(eyre!("profile name cannot be empty!"));
+    }
+    if !name.chars().all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_') {
+        return Err(eyre!("profile name can only contain letters, digits, `-` and `_`!"));
+    }
+    Ok(name.to_owned())
+}
+
+/// returns the names of the profiles having a config file in the given config
+/// directory, the default profile comes first
+pub(crate) fn profile_names(config_dir: &Path, config_file: &str) -> Result<Vec<String>> {
+    let mut names = vec![];
+    if config_dir.join(config_file).is_file() {
+        names.push(DEFAULT_PROFILE.to_owned());
+    }
+
+    let profiles_dir = config_dir.join(PROFILES_DIR);
+    if !profiles_dir.is_dir() {
+        return Ok(names);
+    }
+
+    let mut named = vec![];
+    for entry in std::fs::read_dir(&profiles_dir)
+        .wrap_err_with(|| format!("couldn't read `{}`", profiles_dir.display()))?
+    {
+        let entry = entry.context("couldn't read the profiles directory")?;
+        let Some(name) = entry.file_name().to_str().map(ToOwned::to_owned) else {
+            continue;
+        };
+        if name != DEFAULT_PROFILE
+            && profile_name_parser(&name).is_ok()
+            && entry.path().join(config_file).is_file()
+        {
+            named.push(name);
+        }
+    }
+    named.sort();
+    names.extend(named);
+
+    Ok(names)
+}
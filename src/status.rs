@@ -0,0 +1,253 @@
+//! Status of the `farm` command, served as JSON by the local status API.
+//!
+//! The status API is only served if the config has a `[status]` section, at
+//! `http://<address>/status`, along with `/healthz` and `/readyz` for health
+//! checks, and to `info` through the control socket (see [`crate::control`]).
+//! The status is updated along with the progress bars and the summary, and the
+//! metrics are updated along with it.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use color_eyre::eyre::Result;
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use subspace_sdk::node::BlockNumber;
+use tracing::instrument;
+
+use crate::metrics::Metrics;
+use crate::summary::Summary;
+use crate::utils::serve_http;
+
+/// syncing state of the node
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct NodeStatus {
+    /// whether the initial syncing is completed
+    pub(crate) is_synced: bool,
+    pub(crate) connected_peers: Option<u64>,
+    pub(crate) sync_position: Option<BlockNumber>,
+    pub(crate) sync_target: Option<BlockNumber>,
+    pub(crate) finalized_block: Option<BlockNumber>,
+}
+
+/// initial plotting progress of a plot
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PlotStatus {
+    pub(crate) id: usize,
+    pub(crate) directory: PathBuf,
+    pub(crate) plotted_bytes: u64,
+    pub(crate) allocated_bytes: u64,
+}
+
+/// readiness of the farm, the farm is ready once the node is synced and the
+/// farmer is built, and alive until the subscription to the solutions ends
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Health {
+    pub(crate) is_node_synced: bool,
+    pub(crate) is_farmer_built: bool,
+    pub(crate) is_alive: bool,
+}
+
+impl Health {
+    /// checks whether the farm is ready
+    pub(crate) fn is_ready(&self) -> bool {
+        self.is_node_synced && self.is_farmer_built
+    }
+}
+
+/// status of the farm, as it is served
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct StatusReport {
+    pub(crate) version: String,
+    pub(crate) git_hash: String,
+    pub(crate) chain: Option<String>,
+    pub(crate) uptime_secs: u64,
+    pub(crate) is_farmer_built: bool,
+    pub(crate) node: NodeStatus,
+    pub(crate) plots: Vec<PlotStatus>,
+    /// unknown until the summary is read
+    pub(crate) summary: Option<Summary>,
+}
+
+/// the parts of the status which change while farming
+#[derive(Debug, Default)]
+struct State {
+    chain: Option<String>,
+    is_farmer_built: bool,
+    /// rewards are no longer counted once the subscription to the solutions
+    /// ended
+    is_solution_subscription_ended: bool,
+    node: NodeStatus,
+    plots: BTreeMap<usize, PlotStatus>,
+    summary: Option<Summary>,
+}
+
+/// status of the farm, cheap to clone
+#[derive(Debug, Clone)]
+pub(crate) struct Status {
+    started_at: Instant,
+    state: Arc<Mutex<State>>,
+    metrics: Metrics,
+}
+
+impl Status {
+    /// creates the status of a farm starting now, which updates the given
+    /// metrics
+    pub(crate) fn new(metrics: Metrics) -> Status {
+        Status { started_at: Instant::now(), state: Arc::default(), metrics }
+    }
+
+    /// the state is only ever replaced field by field, so it is still
+    /// consistent if an update panicked
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// records the chain of the node
+    pub(crate) fn set_chain(&self, chain: String) {
+        self.state().chain = Some(chain);
+    }
+
+    /// records the syncing progress of the node
+    pub(crate) fn set_syncing(&self, position: BlockNumber, target: BlockNumber) {
+        let node = &mut self.state().node;
+        node.sync_position = Some(position);
+        node.sync_target = Some(target);
+        self.metrics.set_syncing(position, target);
+    }
+
+    /// records that the initial syncing is completed
+    pub(crate) fn set_synced(&self) {
+        self.state().node.is_synced = true;
+    }
+
+    /// records that the farmer is built
+    pub(crate) fn set_farmer_built(&self) {
+        self.state().is_farmer_built = true;
+    }
+
+    /// records that the subscription to the solutions ended
+    pub(crate) fn set_solution_subscription_ended(&self) {
+        self.state().is_solution_subscription_ended = true;
+    }
+
+    /// records the initial plotting progress of the given plot
+    pub(crate) fn set_plotting(
+        &self,
+        plot_id: usize,
+        directory: &Path,
+        plotted: u64,
+        allocated: u64,
+    ) {
+        self.state().plots.insert(
+            plot_id,
+            PlotStatus {
+                id: plot_id,
+                directory: directory.to_owned(),
+                plotted_bytes: plotted,
+                allocated_bytes: allocated,
+            },
+        );
+        self.metrics.set_plotting(plot_id, plotted, allocated);
+    }
+
+    /// records the given summary
+    pub(crate) fn set_summary(&self, summary: &Summary) {
+        self.state().summary = Some(summary.clone());
+        self.metrics.set_summary(summary);
+    }
+
+    /// records the number of peers the node is connected to
+    pub(crate) fn set_connected_peers(&self, connected_peers: u64) {
+        self.state().node.connected_peers = Some(connected_peers);
+    }
+
+    /// records the last finalized block of the chain
+    pub(crate) fn set_finalized_block(&self, block_number: BlockNumber) {
+        self.state().node.finalized_block = Some(block_number);
+        self.metrics.set_finalized_block(block_number);
+    }
+
+    /// returns the readiness of the farm
+    pub(crate) fn health(&self) -> Health {
+        let state = self.state();
+        Health {
+            is_node_synced: state.node.is_synced,
+            is_farmer_built: state.is_farmer_built,
+            is_alive: !state.is_solution_subscription_ended,
+        }
+    }
+
+    /// returns the current status of the farm
+    pub(crate) fn report(&self) -> StatusReport {
+        let state = self.state();
+        StatusReport {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            git_hash: env!("GIT_HASH").to_owned(),
+            chain: state.chain.clone(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            is_farmer_built: state.is_farmer_built,
+            node: state.node.clone(),
+            plots: state.plots.values().cloned().collect(),
+            summary: state.summary.clone(),
+        }
+    }
+
+    /// responds to a request of the status API
+    fn respond(&self, request: &Request<Body>) -> Response<Body> {
+        let health = self.health();
+        let (status, body) = match (request.method(), request.uri().path()) {
+            (&Method::GET, "/status") => (StatusCode::OK, serde_json::to_string(&self.report())),
+            (&Method::GET, "/healthz") => {
+                let status = match health.is_alive {
+                    true => StatusCode::OK,
+                    false => StatusCode::SERVICE_UNAVAILABLE,
+                };
+                (status, serde_json::to_string(&health))
+            }
+            (&Method::GET, "/readyz") => {
+                let status = match health.is_ready() {
+                    true => StatusCode::OK,
+                    false => StatusCode::SERVICE_UNAVAILABLE,
+                };
+                (status, serde_json::to_string(&health))
+            }
+            _ => return not_found(),
+        };
+
+        match body {
+            Ok(body) => Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap_or_else(|_| Response::new(Body::empty())),
+            Err(err) => {
+                tracing::error!("couldn't serialize the status: {err}");
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                response
+            }
+        }
+    }
+}
+
+/// response to an unknown request
+fn not_found() -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    response
+}
+
+/// binds to the given address, and returns the server of the status API
+#[instrument]
+pub(crate) fn serve_status(
+    address: SocketAddr,
+    status: Status,
+) -> Result<impl Future<Output = Result<()>>> {
+    serve_http(address, "status API", move |request| status.respond(request))
+}
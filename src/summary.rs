@@ -3,24 +3,101 @@
 /// and also store the amount of potentially farmed blocks during the initial
 /// plotting progress, so that progress bar won't be affected with `println!`,
 /// and user will still know about them when initial plotting is finished.
-use std::fs::remove_file;
-use std::path::PathBuf;
+use std::fs::{remove_dir_all, remove_file};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use color_eyre::eyre::{Context, Result};
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Context, Result};
 use derive_more::{AddAssign, Display, From, FromStr};
+use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
 use subspace_sdk::node::BlockNumber;
 use subspace_sdk::ByteSize;
-use tokio::fs::{create_dir_all, File, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::fs::create_dir_all;
 use tokio::sync::Mutex;
 use tracing::instrument;
 
+use crate::home::data_dir_or;
+use crate::output::print_line;
+use crate::profile::profile;
+use crate::summary::ledger::LedgerTotals;
+use crate::summary::lock::{lock_path, LockKind, SummaryLock};
+use crate::summary::storage::{has_summary, open_storage, storage_files, SummaryStorage};
+use crate::utils::format_amount;
+
+pub(crate) mod history;
+pub(crate) mod ledger;
+pub(crate) mod lock;
+pub(crate) mod storage;
+
+/// directory of the summaries, one per chain and reward address
+const SUMMARIES_DIR: &str = "summaries";
+
+/// directory of the summary of the older versions, until the key it was
+/// farmed for is selected
+const LEGACY_SUMMARY_DIR: &str = "legacy";
+
+/// file naming the summary which was farmed last
+const ACTIVE_SUMMARY_FILE: &str = "active";
+
+/// number of the processed blocks kept in the summary, reorgs deeper than it
+/// are reverted to the last reward of the ledger which the chain still has
+pub(crate) const RECENT_BLOCKS: usize = 256;
+
 // TODO: delete this when https://github.com/toml-rs/toml/issues/540 is solved
 #[derive(Debug, Clone, Copy, Default, Display, AddAssign, FromStr, From)]
 pub(crate) struct Rewards(pub(crate) u128);
 
+/// token of the chain, the rewards are paid in
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Token {
+    pub(crate) decimals: u8,
+    pub(crate) symbol: String,
+}
+
+impl Default for Token {
+    /// token of the Subspace chains, for the summaries of the older versions
+    fn default() -> Self {
+        Token { decimals: 18, symbol: "SSC".to_owned() }
+    }
+}
+
+impl Token {
+    /// returns the given amount, in the smallest unit of the token, in the
+    /// given units
+    pub(crate) fn amount(&self, amount: u128, units: Units) -> String {
+        match units {
+            Units::Ssc => format_amount(amount, self.decimals),
+            Units::Base => amount.to_string(),
+        }
+    }
+
+    /// returns the name of the given units
+    pub(crate) fn unit(&self, units: Units) -> &str {
+        match units {
+            Units::Ssc => &self.symbol,
+            Units::Base => "base units",
+        }
+    }
+
+    /// formats the given amount, in the smallest unit of the token, along with
+    /// the name of the given units
+    pub(crate) fn format(&self, amount: u128, units: Units) -> String {
+        format!("{} {}", self.amount(amount, units), self.unit(units))
+    }
+}
+
+/// units the amounts of the token are shown in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Units {
+    /// whole tokens, i.e. SSC
+    #[default]
+    Ssc,
+    /// the smallest unit of the token
+    Base,
+}
+
 /// struct for updating the fields of the summary
 #[derive(Default, Debug)]
 pub(crate) struct SummaryUpdateFields {
@@ -28,12 +105,53 @@ pub(crate) struct SummaryUpdateFields {
     pub(crate) new_authored_count: u64,
     pub(crate) new_vote_count: u64,
     pub(crate) new_reward: Rewards,
-    pub(crate) new_parsed_blocks: BlockNumber,
+    /// number of the next block to process, every block before it is processed
+    pub(crate) last_processed_block_num: Option<BlockNumber>,
+    pub(crate) last_processed_block: Option<ProcessedBlock>,
+    /// blocks processed by the update, in order
+    pub(crate) processed_blocks: Vec<ProcessedBlock>,
+}
+
+/// number and hash of a processed block
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ProcessedBlock {
+    pub(crate) number: BlockNumber,
+    pub(crate) hash: String,
+}
+
+/// chain and reward address a summary is computed for
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SummaryKey {
+    pub(crate) chain: String,
+    pub(crate) genesis_hash: String,
+    pub(crate) reward_address: String,
+}
+
+impl SummaryKey {
+    /// returns the name of the directory of the summary
+    fn dir_name(&self) -> String {
+        let chain = self
+            .chain
+            .to_lowercase()
+            .chars()
+            .map(|char| if char.is_ascii_alphanumeric() { char } else { '-' })
+            .collect::<String>();
+        let genesis_hash = self.genesis_hash.trim_start_matches("0x");
+        let genesis_hash = genesis_hash.get(..16).unwrap_or(genesis_hash);
+        format!("{chain}_{genesis_hash}_{}", self.reward_address)
+    }
+}
+
+impl std::fmt::Display for SummaryKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let SummaryKey { chain, genesis_hash, reward_address } = self;
+        write!(f, "{chain} (genesis {genesis_hash}) for `{reward_address}`")
+    }
 }
 
 /// Struct for holding the info of what to be displayed with the `info` command,
 /// and printing rewards to user in `farm` command
-#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy)]
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
 pub(crate) struct Summary {
     pub(crate) initial_plotting_finished: bool,
     pub(crate) authored_count: u64,
@@ -41,13 +159,36 @@ pub(crate) struct Summary {
     pub(crate) total_rewards: Rewards,
     pub(crate) user_space_pledged: ByteSize,
     pub(crate) last_processed_block_num: BlockNumber,
+    /// chain and reward address of the summary, unknown for the summaries of
+    /// the older versions
+    #[serde(default)]
+    pub(crate) key: Option<SummaryKey>,
+    /// last processed block, the summary is reverted if the chain no longer
+    /// has it (i.e. after a reorg)
+    #[serde(default)]
+    pub(crate) last_processed_block: Option<ProcessedBlock>,
+    /// the last [`RECENT_BLOCKS`] processed blocks, oldest first, to find the
+    /// block a reorg forked from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) recent_blocks: Vec<ProcessedBlock>,
+    /// token the rewards are paid in, unknown for the summaries of the older
+    /// versions
+    #[serde(default)]
+    pub(crate) token: Option<Token>,
 }
 
 /// utilizing persistent storage for the information to be displayed for the
 /// `info` command
+///
+/// the summary is kept in a [`SummaryStorage`], which replaces it atomically,
+/// so a crash leaves either the old or the new summary
 #[derive(Debug, Clone)]
 pub(crate) struct SummaryFile {
-    inner: Arc<Mutex<File>>,
+    /// path of `summary.toml`, the lock file is next to it
+    path: PathBuf,
+    storage: Arc<dyn SummaryStorage>,
+    /// serializes the updates of the summary
+    lock: Arc<Mutex<()>>,
 }
 
 impl SummaryFile {
@@ -56,20 +197,38 @@ impl SummaryFile {
     /// if user_space_pledged is provided, it creates a new summary file
     /// else, it tries to open the existing summary file
     #[instrument]
-    pub(crate) async fn new(user_space_pledged: Option<ByteSize>) -> Result<SummaryFile> {
-        let summary_path = summary_path();
-        let summary_dir = summary_dir();
-
-        let mut summary_file;
-        // providing `Some` value for `user_space_pledged` means, we are creating a new
-        // file, so, first check if the file exists to not erase its content
-        if let Some(user_space_pledged) = user_space_pledged {
-            // File::create will truncate the existing file, so first
-            // check if the file exists, if not, `open` will return an error
-            // in this case, create the file and necessary directories
-            if File::open(&summary_path).await.is_err() {
-                let _ = create_dir_all(&summary_dir).await;
-                let _ = File::create(&summary_path).await;
+    pub(crate) async fn new(
+        summary_dir: &Path,
+        user_space_pledged: Option<ByteSize>,
+    ) -> Result<SummaryFile> {
+        let summary_file = SummaryFile {
+            path: summary_path(summary_dir),
+            storage: open_storage(summary_dir).await?,
+            lock: Arc::new(Mutex::new(())),
+        };
+
+        // the summary is only written if it is created
+        let _file_lock = match user_space_pledged {
+            Some(_) => {
+                create_dir_all(summary_dir).await.context("couldn't create summary directory")?;
+                Some(summary_file.lock_file(LockKind::Exclusive).await?)
+            }
+            // there is nothing to read, the error is reported below
+            None if !summary_dir.exists() => None,
+            None => Some(summary_file.lock_file(LockKind::Shared).await?),
+        };
+
+        match (summary_file.storage.read().await, user_space_pledged) {
+            (Ok(_), _) => (),
+            // providing `Some` value for `user_space_pledged` means, we are creating a new
+            // file, if there is no valid summary to continue from
+            (Err(err), Some(user_space_pledged)) => {
+                if has_summary(summary_dir) {
+                    tracing::warn!("couldn't recover the summary, starting a new one: {err:?}");
+                }
+                // keep the unrecoverable summary around, instead of overwriting it
+                summary_file.storage.set_aside().await?;
+
                 let initialization = Summary {
                     initial_plotting_finished: false,
                     authored_count: 0,
@@ -77,58 +236,26 @@ impl SummaryFile {
                     total_rewards: Rewards(0),
                     user_space_pledged,
                     last_processed_block_num: 0,
+                    key: None,
+                    last_processed_block: None,
+                    recent_blocks: vec![],
+                    token: None,
                 };
-                let summary_text =
-                    toml::to_string(&initialization).context("Failed to serialize Summary")?;
-                summary_file = OpenOptions::new()
-                    .read(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(&summary_path)
-                    .await
-                    .context("couldn't open new summary file")?;
-                summary_file
-                    .write_all(summary_text.as_bytes())
-                    .await
-                    .context("write to summary failed")?;
-                summary_file.flush().await.context("flush at creation failed")?;
-                summary_file
-                    .seek(std::io::SeekFrom::Start(0))
-                    .await
-                    .context("couldn't seek to the beginning of the summary file")?;
-
-                return Ok(SummaryFile { inner: Arc::new(Mutex::new(summary_file)) });
+                summary_file.storage.write(&initialization).await?;
             }
+            // for all the other cases, the SummaryFile should be there
+            (Err(err), None) => return Err(err).context("couldn't open existing summary file"),
         }
-        // for all the other cases, the SummaryFile should be there
-        summary_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&summary_path)
-            .await
-            .context("couldn't open existing summary file")?;
-        Ok(SummaryFile { inner: Arc::new(Mutex::new(summary_file)) })
+
+        Ok(summary_file)
     }
 
     /// parses the summary file and returns [`Summary`]
     #[instrument]
     pub(crate) async fn parse(&self) -> Result<Summary> {
-        let mut guard = self.inner.lock().await;
-        let mut contents = String::new();
-
-        guard
-            .read_to_string(&mut contents)
-            .await
-            .context("couldn't read the contents of the summary file")?;
-        let summary: Summary =
-            toml::from_str(&contents).context("couldn't serialize the summary content")?;
-
-        guard
-            .seek(std::io::SeekFrom::Start(0))
-            .await
-            .context("couldn't seek to the beginning of the summary file")?;
-
-        Ok(summary)
+        let _guard = self.lock.lock().await;
+        let _file_lock = self.lock_file(LockKind::Shared).await?;
+        self.storage.read().await
     }
 
     /// updates the summary file, and returns the content of the new summary
@@ -144,10 +271,16 @@ impl SummaryFile {
             new_authored_count,
             new_vote_count,
             new_reward,
-            new_parsed_blocks,
+            last_processed_block_num,
+            last_processed_block,
+            processed_blocks,
         }: SummaryUpdateFields,
     ) -> Result<Summary> {
-        let mut summary = self.parse().await.context("couldn't parse summary in update method")?;
+        // the lock is held from reading to writing, so concurrent updates are not lost
+        let _guard = self.lock.lock().await;
+        let _file_lock = self.lock_file(LockKind::Exclusive).await?;
+        let mut summary =
+            self.storage.read().await.context("couldn't parse summary in update method")?;
 
         if is_plotting_finished {
             summary.initial_plotting_finished = true;
@@ -159,39 +292,245 @@ impl SummaryFile {
 
         summary.total_rewards += new_reward;
 
-        summary.last_processed_block_num += new_parsed_blocks;
-
-        let serialized_summary =
-            toml::to_string(&summary).context("Failed to serialize Summary")?;
-        let mut guard = self.inner.lock().await;
-        guard.set_len(0).await.context("couldn't truncate the summary file")?;
-        guard
-            .write_all(serialized_summary.as_bytes())
-            .await
-            .context("couldn't write to summary file")?;
-        guard
-            .seek(std::io::SeekFrom::Start(0))
-            .await
-            .context("couldn't seek to the beginning of the summary file")?;
-        guard.flush().await.context("flushing failed for summary file")?;
+        if let Some(last_processed_block_num) = last_processed_block_num {
+            summary.last_processed_block_num = last_processed_block_num;
+        }
+
+        if last_processed_block.is_some() {
+            summary.last_processed_block = last_processed_block;
+        }
+
+        summary.recent_blocks.extend(processed_blocks);
+        let excess = summary.recent_blocks.len().saturating_sub(RECENT_BLOCKS);
+        summary.recent_blocks.drain(..excess);
+
+        self.storage.write(&summary).await?;
+
+        Ok(summary)
+    }
+
+    /// records the chain and reward address the summary is computed for, along
+    /// with the token of the chain
+    #[instrument]
+    pub(crate) async fn set_key(&self, key: SummaryKey, token: Token) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let _file_lock = self.lock_file(LockKind::Exclusive).await?;
+        let mut summary =
+            self.storage.read().await.context("couldn't parse summary in set key method")?;
+
+        if summary.key.as_ref() != Some(&key) || summary.token.as_ref() != Some(&token) {
+            summary.key = Some(key);
+            summary.token = Some(token);
+            self.storage.write(&summary).await?;
+        }
+
+        Ok(())
+    }
+
+    /// resets the rewards and the processed blocks of the summary, so that they
+    /// are derived from the chain again
+    #[instrument]
+    pub(crate) async fn reset_rewards(&self) -> Result<Summary> {
+        let _guard = self.lock.lock().await;
+        let _file_lock = self.lock_file(LockKind::Exclusive).await?;
+        let mut summary =
+            self.storage.read().await.context("couldn't parse summary in reset method")?;
+
+        summary.authored_count = 0;
+        summary.vote_count = 0;
+        summary.total_rewards = Rewards(0);
+        summary.last_processed_block_num = 0;
+        summary.last_processed_block = None;
+        summary.recent_blocks.clear();
+
+        self.storage.write(&summary).await?;
 
         Ok(summary)
     }
+
+    /// reverts the given totals of the summary (i.e. of the blocks reverted by
+    /// a reorg), and continues from the block after the given one
+    #[instrument]
+    pub(crate) async fn revert(
+        &self,
+        reverted: LedgerTotals,
+        last_processed_block: Option<ProcessedBlock>,
+    ) -> Result<Summary> {
+        let _guard = self.lock.lock().await;
+        let _file_lock = self.lock_file(LockKind::Exclusive).await?;
+        let mut summary =
+            self.storage.read().await.context("couldn't parse summary in revert method")?;
+
+        summary.authored_count = summary.authored_count.saturating_sub(reverted.authored_count);
+        summary.vote_count = summary.vote_count.saturating_sub(reverted.vote_count);
+        summary.total_rewards =
+            Rewards(summary.total_rewards.0.saturating_sub(reverted.total_rewards));
+        summary.last_processed_block_num =
+            last_processed_block.as_ref().map_or(0, |block| block.number + 1);
+        summary.recent_blocks.retain(|block| {
+            last_processed_block
+                .as_ref()
+                .is_some_and(|fork_point| block.number <= fork_point.number)
+        });
+        summary.last_processed_block = last_processed_block;
+
+        self.storage.write(&summary).await?;
+
+        Ok(summary)
+    }
+
+    /// waits for the lock of the summary file, shared with the other processes
+    ///
+    /// the lock of the summary has to be held first, as the processes are
+    /// locked out, not the tasks of this one
+    async fn lock_file(&self, kind: LockKind) -> Result<SummaryLock> {
+        SummaryLock::acquire(&self.path, kind).await.context("couldn't lock the summary file")
+    }
 }
 
-/// deletes the summary file
+/// deletes the summary in the given directory, along with the reward ledger
+/// derived with it
 #[instrument]
-pub(crate) fn delete_summary() -> Result<()> {
-    remove_file(summary_path()).context("couldn't delete summary file")
+pub(crate) fn delete_summary(summary_dir: &Path) -> Result<()> {
+    let had_summary = has_summary(summary_dir);
+    for path in storage_files(summary_dir) {
+        let _ = remove_file(path);
+    }
+    let _ = remove_file(lock_path(&summary_path(summary_dir)));
+
+    match had_summary {
+        true => Ok(()),
+        false =>
+            Err(eyre!("couldn't delete summary file, there is none at `{}`", summary_dir.display())),
+    }
+}
+
+/// selects the summary of the given key as the active one, and returns its
+/// directory in the given summary directory
+///
+/// the summary of the older versions is not scoped to a chain and reward
+/// address, so it is only moved to the directory of the key it was farmed for:
+/// the one it records, or the one of the config the older version farmed with
+/// (`is_original_key`). Otherwise it is kept in the `legacy` directory, until
+/// its key is selected.
+#[instrument]
+pub(crate) fn activate_summary(
+    summary_dir: &Path,
+    key: &SummaryKey,
+    is_original_key: bool,
+) -> Result<PathBuf> {
+    let summaries_dir = summary_dir.join(SUMMARIES_DIR);
+    let key_dir = summaries_dir.join(key.dir_name());
+    let legacy_dir = summaries_dir.join(LEGACY_SUMMARY_DIR);
+    std::fs::create_dir_all(&key_dir).context("couldn't create summary directory")?;
+
+    let unscoped_dir = [summary_dir, &legacy_dir].into_iter().find(|dir| has_summary(dir));
+    if let Some(unscoped_dir) = unscoped_dir.filter(|_| !has_summary(&key_dir)) {
+        let is_adopted = match legacy_summary_key(unscoped_dir) {
+            Some(legacy_key) => legacy_key == *key,
+            None => is_original_key,
+        };
+        if is_adopted {
+            move_storage_files(unscoped_dir, &key_dir)?;
+            // the legacy directory is only left if it is empty
+            let _ = std::fs::remove_dir(&legacy_dir);
+        } else if unscoped_dir == summary_dir {
+            std::fs::create_dir_all(&legacy_dir).context("couldn't create summary directory")?;
+            move_storage_files(summary_dir, &legacy_dir)?;
+            print_line(format!(
+                "{} the summary of the older version is not for {key}, it is kept at `{}`",
+                "warning:".yellow(),
+                legacy_dir.display()
+            ));
+        }
+    }
+
+    std::fs::write(summary_dir.join(ACTIVE_SUMMARY_FILE), key.dir_name())
+        .context("couldn't write the active summary")?;
+    Ok(key_dir)
+}
+
+/// returns the key recorded in the unscoped summary of the given directory
+fn legacy_summary_key(dir: &Path) -> Option<SummaryKey> {
+    /// the only field of the summary needed
+    #[derive(Deserialize)]
+    struct LegacySummary {
+        key: Option<SummaryKey>,
+    }
+
+    let summary = std::fs::read_to_string(summary_path(dir)).ok()?;
+    toml::from_str::<LegacySummary>(&summary).ok()?.key
+}
+
+/// moves the files of the summary in the given directory to another one
+fn move_storage_files(from: &Path, to: &Path) -> Result<()> {
+    for path in storage_files(from) {
+        if let Some(file_name) = path.file_name().filter(|_| path.exists()) {
+            std::fs::rename(&path, to.join(file_name))
+                .wrap_err_with(|| format!("couldn't move `{}`", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// returns the directory of the active summary in the given summary
+/// directory, the one which was farmed last
+#[instrument]
+pub(crate) fn active_summary_dir(summary_dir: &Path) -> PathBuf {
+    match std::fs::read_to_string(summary_dir.join(ACTIVE_SUMMARY_FILE)) {
+        Ok(dir_name) => summary_dir.join(SUMMARIES_DIR).join(dir_name.trim()),
+        // summaries of the older versions are not scoped
+        Err(_) => summary_dir.to_owned(),
+    }
+}
+
+/// returns the directories of all the summaries in the given summary directory
+#[instrument]
+pub(crate) fn summary_dirs(summary_dir: &Path) -> Result<Vec<PathBuf>> {
+    let summaries_dir = summary_dir.join(SUMMARIES_DIR);
+    if !summaries_dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut summary_dirs = vec![];
+    for entry in std::fs::read_dir(&summaries_dir)
+        .wrap_err_with(|| format!("couldn't read `{}`", summaries_dir.display()))?
+    {
+        let path = entry.context("couldn't read the summaries directory")?.path();
+        if has_summary(&path) {
+            summary_dirs.push(path);
+        }
+    }
+    summary_dirs.sort();
+    Ok(summary_dirs)
+}
+
+/// deletes all the summaries in the given summary directory
+#[instrument]
+pub(crate) fn delete_summaries(summary_dir: &Path) -> Result<()> {
+    let summaries_dir = summary_dir.join(SUMMARIES_DIR);
+    let had_summaries = summaries_dir.exists();
+    if had_summaries {
+        remove_dir_all(&summaries_dir).context("couldn't delete the summaries")?;
+    }
+    let _ = remove_file(summary_dir.join(ACTIVE_SUMMARY_FILE));
+
+    match delete_summary(summary_dir) {
+        Err(_) if had_summaries => Ok(()),
+        result => result,
+    }
 }
 
-/// returns the path for the summary file
+/// returns the path for the summary file in the given directory
 #[instrument]
-pub(crate) fn summary_path() -> PathBuf {
-    summary_dir().join("summary.toml")
+pub(crate) fn summary_path(summary_dir: &Path) -> PathBuf {
+    summary_dir.join("summary.toml")
 }
 
+/// returns the default directory of the summary file
 #[instrument]
-fn summary_dir() -> PathBuf {
-    dirs::cache_dir().expect("couldn't get the  directory!").join("subspace-cli")
+pub(crate) fn summary_dir() -> PathBuf {
+    profile().namespace(data_dir_or(|| {
+        dirs::cache_dir().expect("couldn't get the  directory!").join("subspace-cli")
+    }))
 }
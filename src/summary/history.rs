@@ -0,0 +1,122 @@
+//! Rewards, votes and authored blocks of the farmer per period of time, derived
+//! from the reward ledger.
+//!
+//! Periods are in UTC, weeks start on Monday.
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime};
+use clap::ValueEnum;
+
+use crate::summary::ledger::{LedgerRecord, LedgerTotals};
+
+/// bars of the sparkline, from the lowest to the highest
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// length of the periods of the history
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Period {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// returns the first day of the period containing the given date
+    pub(crate) fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+            Period::Month => date.with_day(1).expect("every month has a first day"),
+        }
+    }
+
+    /// returns the first day of the period after the one starting at the given
+    /// date
+    fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => start + Duration::days(1),
+            Period::Week => start + Duration::weeks(1),
+            Period::Month => start + Months::new(1),
+        }
+    }
+
+    /// returns the label of the period starting at the given date
+    pub(crate) fn label(self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d"),
+            Period::Week => start.format("%G-W%V"),
+            Period::Month => start.format("%Y-%m"),
+        }
+        .to_string()
+    }
+}
+
+/// returns the date of the given unix timestamp
+pub(crate) fn timestamp_date(timestamp: u64) -> Option<NaiveDate> {
+    let timestamp = i64::try_from(timestamp).ok()?;
+    NaiveDateTime::from_timestamp_opt(timestamp, 0).map(|date_time| date_time.date())
+}
+
+/// returns the unix timestamp of the start of the given date
+pub(crate) fn date_timestamp(date: NaiveDate) -> u64 {
+    let start = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+    u64::try_from(start.timestamp()).unwrap_or_default()
+}
+
+/// returns the current date
+pub(crate) fn today() -> NaiveDate {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    timestamp_date(now).unwrap_or_default()
+}
+
+/// returns the totals of every period, from the one of `since` (or of the first
+/// record) to the one of `until`
+///
+/// periods without any records are included, so that a drop in the rewards is
+/// visible. Records without a timestamp (i.e. of pruned blocks) are skipped.
+pub(crate) fn history(
+    records: &[LedgerRecord],
+    by: Period,
+    since: Option<NaiveDate>,
+    until: NaiveDate,
+) -> Vec<(NaiveDate, LedgerTotals)> {
+    let mut periods = BTreeMap::<NaiveDate, LedgerTotals>::new();
+    for record in records {
+        let Some(date) = record.timestamp.and_then(timestamp_date) else {
+            continue;
+        };
+        if since.is_some_and(|since| date < since) {
+            continue;
+        }
+        periods.entry(by.start(date)).or_default().add(record);
+    }
+
+    let Some(mut start) = since.map(|since| by.start(since)).or(periods.keys().next().copied())
+    else {
+        return vec![];
+    };
+    // records in the future (i.e. a clock behind the chain) are still shown
+    let end = periods.keys().next_back().copied().unwrap_or(until).max(by.start(until));
+
+    let mut history = vec![];
+    while start <= end {
+        history.push((start, periods.remove(&start).unwrap_or_default()));
+        start = by.next(start);
+    }
+    history
+}
+
+/// renders the given values as a sparkline, one bar per value
+pub(crate) fn sparkline(values: &[u128]) -> String {
+    let max = values.iter().copied().max().unwrap_or_default();
+    values
+        .iter()
+        .map(|&value| match max {
+            0 => SPARKS[0],
+            max => SPARKS[(value as f64 / max as f64 * (SPARKS.len() - 1) as f64).round() as usize],
+        })
+        .collect()
+}
@@ -0,0 +1,253 @@
+//! Append-only ledger of the rewards of the farmer.
+//!
+//! The summary only keeps running totals, while the ledger keeps a record for
+//! every reward and vote of the farmer, next to the summary file. So it is
+//! known which block paid what, and when, and the totals of the summary can be
+//! derived from the ledger (see [`LedgerTotals`]).
+//!
+//! Records are only ever appended to the ledger, in the order of the blocks.
+//! The only exception is a reorg, after which the records of the reverted
+//! blocks are removed from the end of the ledger. See
+//! [`crate::summary::storage`] for how the ledger is stored.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use strum_macros::{Display, EnumString};
+use subspace_sdk::node::BlockNumber;
+use tokio::sync::Mutex;
+use tracing::instrument;
+
+use crate::summary::storage::{open_storage, RecordQuery, SummaryStorage};
+use crate::summary::Summary;
+
+/// kind of a record in the ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum RewardKind {
+    /// reward for authoring the block
+    BlockReward,
+    /// reward for a vote included in the block
+    VoteReward,
+    /// vote included in the block
+    Vote,
+}
+
+/// a reward or a vote of the farmer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LedgerRecord {
+    pub(crate) block_number: BlockNumber,
+    pub(crate) block_hash: String,
+    /// unix timestamp of the block in seconds, unknown if the block is pruned
+    pub(crate) timestamp: Option<u64>,
+    pub(crate) kind: RewardKind,
+    /// amount in the smallest unit of the token, zero for votes
+    pub(crate) amount: u128,
+}
+
+impl fmt::Display for LedgerRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let LedgerRecord { block_number, block_hash, timestamp, kind, amount } = self;
+        let timestamp = timestamp.map(|timestamp| timestamp.to_string()).unwrap_or_default();
+        write!(f, "{block_number},{block_hash},{timestamp},{kind},{amount}")
+    }
+}
+
+impl FromStr for LedgerRecord {
+    type Err = color_eyre::Report;
+
+    fn from_str(record: &str) -> Result<Self> {
+        let fields = record.split(',').collect::<Vec<_>>();
+        let [block_number, block_hash, timestamp, kind, amount] = fields[..] else {
+            return Err(eyre!("expected 5 fields, found {}", fields.len()));
+        };
+
+        Ok(LedgerRecord {
+            block_number: block_number.parse().context("invalid block number")?,
+            block_hash: block_hash.to_owned(),
+            timestamp: match timestamp {
+                "" => None,
+                timestamp => Some(timestamp.parse().context("invalid timestamp")?),
+            },
+            kind: kind.parse().wrap_err_with(|| format!("unknown kind `{kind}`"))?,
+            amount: amount.parse().context("invalid amount")?,
+        })
+    }
+}
+
+/// totals of the summary, derived from the records of the ledger
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LedgerTotals {
+    pub(crate) authored_count: u64,
+    pub(crate) vote_count: u64,
+    pub(crate) total_rewards: u128,
+}
+
+impl LedgerTotals {
+    /// sums up the given records
+    pub(crate) fn from_records(records: &[LedgerRecord]) -> LedgerTotals {
+        let mut totals = LedgerTotals::default();
+        for record in records {
+            totals.add(record);
+        }
+        totals
+    }
+
+    /// adds the given record to the totals
+    pub(crate) fn add(&mut self, record: &LedgerRecord) {
+        match record.kind {
+            RewardKind::BlockReward => self.authored_count += 1,
+            RewardKind::Vote => self.vote_count += 1,
+            RewardKind::VoteReward => (),
+        }
+        self.total_rewards += record.amount;
+    }
+
+    /// checks whether the totals of the summary match the ones of the ledger
+    ///
+    /// authored blocks are left out, as the summary counts them from the
+    /// pre-digests of the blocks, while the ledger only knows about the ones
+    /// with a block reward
+    pub(crate) fn check(&self, summary: &Summary) -> Result<()> {
+        let mismatches = [
+            ("vote(s)", self.vote_count as u128, summary.vote_count as u128),
+            ("rewards", self.total_rewards, summary.total_rewards.0),
+        ]
+        .into_iter()
+        .filter(|(_, in_ledger, in_summary)| in_ledger != in_summary)
+        .map(|(name, in_ledger, in_summary)| {
+            format!("{name}: {in_ledger} in the ledger, {in_summary} in the summary")
+        })
+        .collect::<Vec<_>>();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre!("reward ledger does not match the summary ({})", mismatches.join(", ")))
+        }
+    }
+}
+
+/// appends the rewards and votes of the farmer to the ledger
+#[derive(Debug, Clone)]
+pub(crate) struct Ledger {
+    storage: Arc<dyn SummaryStorage>,
+    /// records of the last block in the ledger
+    ///
+    /// after a crash, the blocks recorded since the last update of the summary
+    /// are processed again, so their records are skipped instead of being
+    /// appended twice
+    tail: Arc<Mutex<Vec<LedgerRecord>>>,
+}
+
+impl Ledger {
+    /// opens the ledger in the given summary directory, creating it if it does
+    /// not exist
+    #[instrument]
+    pub(crate) async fn open(summary_dir: &Path) -> Result<Ledger> {
+        let storage = open_storage(summary_dir).await?;
+        storage.open_ledger().await?;
+        let tail = storage.last_records().await?;
+        Ok(Ledger { storage, tail: Arc::new(Mutex::new(tail)) })
+    }
+
+    /// returns the records of the ledger
+    pub(crate) async fn records(&self) -> Result<Vec<LedgerRecord>> {
+        self.storage.records(RecordQuery::default()).await
+    }
+
+    /// removes the records of the blocks after the given one (i.e. reverted by
+    /// a reorg), and returns them
+    #[instrument]
+    pub(crate) async fn revert_after(
+        &self,
+        block_number: Option<BlockNumber>,
+    ) -> Result<Vec<LedgerRecord>> {
+        let mut tail = self.tail.lock().await;
+        let reverted = self.storage.revert_records_after(block_number).await?;
+        if !reverted.is_empty() {
+            *tail = self.storage.last_records().await?;
+        }
+        Ok(reverted)
+    }
+
+    /// appends the given records to the ledger, skipping the ones already in
+    /// it
+    ///
+    /// records are expected to be appended in the order of the blocks
+    #[instrument(skip(records))]
+    pub(crate) async fn append(&self, mut records: Vec<LedgerRecord>) -> Result<()> {
+        records.sort_by_key(|record| record.block_number);
+
+        let mut tail = self.tail.lock().await;
+        let last_block_number = tail.first().map(|record| record.block_number);
+        let mut already_appended = tail.clone();
+        let mut new_tail = tail.clone();
+        let mut new_records = vec![];
+
+        for record in records {
+            match last_block_number {
+                Some(last_block_number) if record.block_number < last_block_number => continue,
+                Some(last_block_number) if record.block_number == last_block_number => {
+                    if let Some(index) = already_appended.iter().position(|old| *old == record) {
+                        already_appended.swap_remove(index);
+                        continue;
+                    }
+                }
+                _ => (),
+            }
+
+            if new_tail.first().is_some_and(|last| last.block_number != record.block_number) {
+                new_tail.clear();
+            }
+            new_records.push(record.clone());
+            new_tail.push(record);
+        }
+
+        if new_records.is_empty() {
+            return Ok(());
+        }
+
+        self.storage.append_records(&new_records).await?;
+
+        *tail = new_tail;
+        Ok(())
+    }
+}
+
+/// returns the records of the last block
+pub(crate) fn tail_of(records: &[LedgerRecord]) -> Vec<LedgerRecord> {
+    match records.last() {
+        Some(last) => records
+            .iter()
+            .filter(|record| record.block_number == last.block_number)
+            .cloned()
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// reads the records of the ledger in the given summary directory
+#[instrument]
+pub(crate) async fn read_ledger(summary_dir: &Path) -> Result<Vec<LedgerRecord>> {
+    query_ledger(summary_dir, RecordQuery::default()).await
+}
+
+/// reads the records of the ledger in the given summary directory, which match
+/// the given query
+#[instrument]
+pub(crate) async fn query_ledger(
+    summary_dir: &Path,
+    query: RecordQuery,
+) -> Result<Vec<LedgerRecord>> {
+    open_storage(summary_dir).await?.records(query).await
+}
+
+/// returns the path of the ledger file in the given summary directory
+#[instrument]
+pub(crate) fn ledger_path(summary_dir: &Path) -> PathBuf {
+    summary_dir.join("ledger.csv")
+}
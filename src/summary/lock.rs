@@ -0,0 +1,68 @@
+//! OS-level advisory lock of the summary file.
+//!
+//! The summary is shared between processes, i.e. `info` may run while `farm`
+//! is updating the summary. Readers hold a shared lock and writers an
+//! exclusive one, so a reader never sees the summary in the middle of an
+//! update.
+//!
+//! The summary file itself is replaced on every write, so the lock is taken on
+//! a separate lock file next to it.
+
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Context, Result};
+use fs2::FileExt;
+use tracing::instrument;
+
+/// kind of the lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LockKind {
+    /// for reading the summary, held by any number of processes at once
+    Shared,
+    /// for writing the summary, held by a single process at once
+    Exclusive,
+}
+
+/// holds the lock of the summary until it is dropped
+#[derive(Debug)]
+pub(crate) struct SummaryLock {
+    file: File,
+}
+
+impl SummaryLock {
+    /// waits for the lock of the summary at the given path
+    #[instrument]
+    pub(crate) async fn acquire(summary_path: &Path, kind: LockKind) -> Result<SummaryLock> {
+        let path = lock_path(summary_path);
+        // locking blocks the thread until the other processes release the lock
+        tokio::task::spawn_blocking(move || {
+            let file =
+                OpenOptions::new().create(true).write(true).open(&path).wrap_err_with(|| {
+                    format!("couldn't open the lock file `{}`", path.display())
+                })?;
+            match kind {
+                LockKind::Shared => file.lock_shared(),
+                LockKind::Exclusive => file.lock_exclusive(),
+            }
+            .wrap_err_with(|| format!("couldn't lock `{}`", path.display()))?;
+            Ok(SummaryLock { file })
+        })
+        .await
+        .context("locking the summary panicked")?
+    }
+}
+
+impl Drop for SummaryLock {
+    fn drop(&mut self) {
+        // closing the file releases the lock as well, so the error is only logged
+        if let Err(err) = self.file.unlock() {
+            tracing::warn!("couldn't unlock the summary: {err}");
+        }
+    }
+}
+
+/// returns the path of the lock file of the summary at the given path
+pub(crate) fn lock_path(summary_path: &Path) -> PathBuf {
+    summary_path.with_extension("lock")
+}
@@ -0,0 +1,206 @@
+//! Storage of the summary, along with its reward ledger.
+//!
+//! The summary is stored either in TOML and CSV files (the default, and the
+//! only storage of the older versions), or in an SQLite database, which only
+//! writes what changed and answers the queries of the ledger with its indexes.
+//! The storage is chosen with `summary_storage` in the `farmer` section of the
+//! config, and an existing summary is migrated to the database once `farm` is
+//! started with `summary_storage = "sqlite"`.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumString;
+use subspace_sdk::node::BlockNumber;
+use tracing::instrument;
+
+use crate::output::print_line;
+use crate::summary::ledger::{ledger_path, LedgerRecord};
+use crate::summary::{summary_path, Summary};
+
+pub(crate) mod file;
+pub(crate) mod sqlite;
+
+use file::FileStorage;
+use sqlite::SqliteStorage;
+
+/// extension of the files of the summary, after they are migrated to the
+/// database
+const MIGRATED_EXTENSION: &str = "migrated";
+
+/// kind of the storage of the summary
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub(crate) enum StorageKind {
+    /// `summary.toml` and `ledger.csv`
+    #[default]
+    Toml,
+    /// `summary.db`
+    Sqlite,
+}
+
+/// records of the ledger to query, every record by default
+///
+/// records without a timestamp (i.e. of pruned blocks) do not match any
+/// range of timestamps
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RecordQuery {
+    pub(crate) from_block: Option<BlockNumber>,
+    /// inclusive
+    pub(crate) to_block: Option<BlockNumber>,
+    pub(crate) from_timestamp: Option<u64>,
+    /// inclusive
+    pub(crate) to_timestamp: Option<u64>,
+}
+
+impl RecordQuery {
+    /// checks whether the given record matches the query
+    pub(crate) fn matches(&self, record: &LedgerRecord) -> bool {
+        let RecordQuery { from_block, to_block, from_timestamp, to_timestamp } = *self;
+        let is_in_timestamps = match record.timestamp {
+            Some(timestamp) =>
+                from_timestamp.map_or(true, |from| timestamp >= from)
+                    && to_timestamp.map_or(true, |to| timestamp <= to),
+            None => from_timestamp.is_none() && to_timestamp.is_none(),
+        };
+        from_block.map_or(true, |from| record.block_number >= from)
+            && to_block.map_or(true, |to| record.block_number <= to)
+            && is_in_timestamps
+    }
+}
+
+/// storage of a summary and its reward ledger
+///
+/// records are kept in the order they are appended, which is the order of the
+/// blocks
+#[async_trait]
+pub(crate) trait SummaryStorage: fmt::Debug + Send + Sync {
+    /// reads the summary
+    async fn read(&self) -> Result<Summary>;
+
+    /// atomically replaces the summary with the given one
+    async fn write(&self, summary: &Summary) -> Result<()>;
+
+    /// keeps the unrecoverable summary around, before it is replaced by a new
+    /// one
+    async fn set_aside(&self) -> Result<()>;
+
+    /// creates the ledger if it does not exist, and drops an incomplete
+    /// record at its end (i.e. after a crash)
+    async fn open_ledger(&self) -> Result<()>;
+
+    /// returns the records of the ledger matching the given query
+    async fn records(&self, query: RecordQuery) -> Result<Vec<LedgerRecord>>;
+
+    /// returns the records of the last block in the ledger
+    async fn last_records(&self) -> Result<Vec<LedgerRecord>>;
+
+    /// appends the given records to the ledger
+    async fn append_records(&self, records: &[LedgerRecord]) -> Result<()>;
+
+    /// removes the records of the blocks after the given one, and returns
+    /// them
+    async fn revert_records_after(
+        &self,
+        block_number: Option<BlockNumber>,
+    ) -> Result<Vec<LedgerRecord>>;
+}
+
+/// opens the storage of the summary in the given directory, the database if
+/// there is one
+#[instrument]
+pub(crate) async fn open_storage(summary_dir: &Path) -> Result<Arc<dyn SummaryStorage>> {
+    if db_path(summary_dir).exists() {
+        Ok(Arc::new(SqliteStorage::open(summary_dir).await?))
+    } else {
+        Ok(Arc::new(FileStorage::new(summary_dir)))
+    }
+}
+
+/// migrates the summary in the given directory to the given kind of storage,
+/// if it is not stored in it yet
+///
+/// the files of the migrated summary are kept with the `.migrated` extension.
+/// Once the summary is in the database, it stays there.
+#[instrument]
+pub(crate) async fn migrate_storage(summary_dir: &Path, kind: StorageKind) -> Result<()> {
+    let db_path = db_path(summary_dir);
+    match kind {
+        StorageKind::Sqlite if !db_path.exists() => (),
+        StorageKind::Sqlite => return Ok(()),
+        StorageKind::Toml => {
+            if db_path.exists() {
+                tracing::warn!(
+                    "the summary is stored in `{}`, and it is not migrated back to TOML",
+                    db_path.display()
+                );
+            }
+            return Ok(());
+        }
+    }
+
+    let file_storage = FileStorage::new(summary_dir);
+    // a new summary is created in the database by `farm`
+    let summary = file_storage.read().await.ok();
+    let records = match (&summary, ledger_path(summary_dir).exists()) {
+        (_, true) => file_storage.records(RecordQuery::default()).await?,
+        (None, false) => vec![],
+        (Some(_), false) => {
+            // the ledger of the processed blocks is rebuilt from the chain first
+            print_line("The summary will be migrated to the database on the next start.");
+            return Ok(());
+        }
+    };
+
+    std::fs::create_dir_all(summary_dir).context("couldn't create summary directory")?;
+    SqliteStorage::create(summary_dir, summary.as_ref(), &records)
+        .await
+        .context("couldn't migrate the summary to the database")?;
+
+    for path in file::files(summary_dir) {
+        if path.exists() {
+            std::fs::rename(&path, migrated_path(&path))
+                .wrap_err_with(|| format!("couldn't move `{}`", path.display()))?;
+        }
+    }
+    if summary.is_some() {
+        print_line(format!("Migrated the summary to `{}`", db_path.display()));
+    }
+
+    Ok(())
+}
+
+/// checks whether there is a summary in the given directory
+pub(crate) fn has_summary(summary_dir: &Path) -> bool {
+    summary_path(summary_dir).is_file() || db_path(summary_dir).is_file()
+}
+
+/// checks whether there is a reward ledger in the given directory
+pub(crate) fn has_ledger(summary_dir: &Path) -> bool {
+    ledger_path(summary_dir).is_file() || db_path(summary_dir).is_file()
+}
+
+/// returns the files of both storages in the given directory, along with the
+/// migrated ones, whether they exist or not
+pub(crate) fn storage_files(summary_dir: &Path) -> Vec<PathBuf> {
+    let files = file::files(summary_dir);
+    let migrated_files = files.iter().map(|path| migrated_path(path)).collect::<Vec<_>>();
+    files.into_iter().chain(sqlite::db_files(summary_dir)).chain(migrated_files).collect()
+}
+
+/// returns the path of the given file, after it is migrated to the database
+fn migrated_path(path: &Path) -> PathBuf {
+    let mut migrated_path = path.as_os_str().to_owned();
+    migrated_path.push(format!(".{MIGRATED_EXTENSION}"));
+    migrated_path.into()
+}
+
+/// returns the path of the database of the summary in the given directory
+pub(crate) fn db_path(summary_dir: &Path) -> PathBuf {
+    summary_dir.join("summary.db")
+}
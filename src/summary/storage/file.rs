@@ -0,0 +1,250 @@
+//! Storage of the summary in `summary.toml`, and of its reward ledger in
+//! `ledger.csv`.
+//!
+//! The summary is never modified in place: a new version is written to a
+//! temporary file, synced to the disk, and renamed over the summary, while the
+//! previous version is kept as a backup. So a crash leaves either the old or
+//! the new summary, and a corrupt summary is recovered from the backup.
+//!
+//! The ledger is a CSV file with a header, records are only ever appended to
+//! it. The only exception is a reorg, after which the records of the reverted
+//! blocks are removed from the end of the ledger.
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Context, Result};
+use subspace_sdk::node::BlockNumber;
+use tokio::fs::{create_dir_all, read_to_string, rename, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::instrument;
+
+use super::{RecordQuery, SummaryStorage};
+use crate::summary::ledger::{ledger_path, tail_of, LedgerRecord};
+use crate::summary::{summary_path, Summary};
+
+/// header of the ledger file, naming the fields of the records
+const LEDGER_HEADER: &str = "block_number,block_hash,timestamp,kind,amount";
+
+/// `summary.toml` and `ledger.csv` in the summary directory
+#[derive(Debug, Clone)]
+pub(crate) struct FileStorage {
+    summary_dir: PathBuf,
+    path: PathBuf,
+    ledger_path: PathBuf,
+}
+
+impl FileStorage {
+    /// returns the storage in the given summary directory, the files are
+    /// created once they are written
+    pub(crate) fn new(summary_dir: &Path) -> FileStorage {
+        FileStorage {
+            summary_dir: summary_dir.to_owned(),
+            path: summary_path(summary_dir),
+            ledger_path: ledger_path(summary_dir),
+        }
+    }
+
+    /// reads the complete records of the ledger
+    async fn read_ledger(&self) -> Result<Vec<LedgerRecord>> {
+        let contents = read_to_string(&self.ledger_path).await.wrap_err_with(|| {
+            format!("couldn't read the ledger at `{}`", self.ledger_path.display())
+        })?;
+        parse_ledger(&contents)
+    }
+}
+
+#[async_trait]
+impl SummaryStorage for FileStorage {
+    /// reads the summary, falling back to the backup if the summary is missing
+    /// or corrupt
+    async fn read(&self) -> Result<Summary> {
+        let err = match read_summary(&self.path).await {
+            Ok(summary) => return Ok(summary),
+            Err(err) => err,
+        };
+
+        let backup = read_summary(&backup_path(&self.path))
+            .await
+            .map_err(|_| err.wrap_err("couldn't read the summary file, nor its backup"))?;
+        tracing::warn!("summary file is missing or corrupt, recovered it from the backup");
+        Ok(backup)
+    }
+
+    /// the current summary becomes the backup, unless it is corrupt
+    async fn write(&self, summary: &Summary) -> Result<()> {
+        let summary_text = toml::to_string(summary).context("Failed to serialize Summary")?;
+        let temp_path = self.path.with_extension("toml.tmp");
+
+        let mut temp_file =
+            File::create(&temp_path).await.context("couldn't create temporary summary file")?;
+        temp_file
+            .write_all(summary_text.as_bytes())
+            .await
+            .context("couldn't write to summary file")?;
+        temp_file.sync_all().await.context("couldn't sync the summary file to the disk")?;
+        drop(temp_file);
+
+        if read_summary(&self.path).await.is_ok() {
+            rename(&self.path, backup_path(&self.path))
+                .await
+                .context("couldn't back up the summary file")?;
+        }
+        rename(&temp_path, &self.path).await.context("couldn't replace the summary file")?;
+
+        // the renames are only durable once the directory itself is synced
+        #[cfg(unix)]
+        File::open(&self.summary_dir)
+            .await
+            .context("couldn't open the summary directory")?
+            .sync_all()
+            .await
+            .context("couldn't sync the summary directory to the disk")?;
+
+        Ok(())
+    }
+
+    /// moves the corrupt summary file next to it
+    async fn set_aside(&self) -> Result<()> {
+        if self.path.exists() {
+            let corrupt_path = self.path.with_extension("toml.corrupt");
+            tracing::warn!(
+                "couldn't recover the summary file, moving it to `{}` and starting a new one",
+                corrupt_path.display()
+            );
+            rename(&self.path, &corrupt_path)
+                .await
+                .context("couldn't move the corrupt summary file")?;
+        }
+        Ok(())
+    }
+
+    #[instrument]
+    async fn open_ledger(&self) -> Result<()> {
+        let mut contents = match self.ledger_path.exists() {
+            true =>
+                read_to_string(&self.ledger_path).await.context("couldn't read the ledger file")?,
+            false => {
+                create_dir_all(&self.summary_dir)
+                    .await
+                    .context("couldn't create summary directory")?;
+                String::new()
+            }
+        };
+
+        // a crash while appending may leave an incomplete record at the end
+        if !contents.ends_with('\n') {
+            let complete = contents.rfind('\n').map_or(0, |index| index + 1);
+            if complete < contents.len() {
+                tracing::warn!("dropping the incomplete last record of the ledger");
+            }
+            contents.truncate(complete);
+            let mut file =
+                File::create(&self.ledger_path).await.context("couldn't create the ledger file")?;
+            if contents.is_empty() {
+                contents = format!("{LEDGER_HEADER}\n");
+            }
+            file.write_all(contents.as_bytes()).await.context("couldn't write the ledger file")?;
+            file.sync_all().await.context("couldn't sync the ledger file to the disk")?;
+        }
+
+        // the records are validated once opened
+        parse_ledger(&contents).map(drop)
+    }
+
+    /// the whole ledger is read, and filtered
+    async fn records(&self, query: RecordQuery) -> Result<Vec<LedgerRecord>> {
+        let records = self.read_ledger().await?;
+        Ok(records.into_iter().filter(|record| query.matches(record)).collect())
+    }
+
+    async fn last_records(&self) -> Result<Vec<LedgerRecord>> {
+        Ok(tail_of(&self.read_ledger().await?))
+    }
+
+    async fn append_records(&self, records: &[LedgerRecord]) -> Result<()> {
+        let lines = records.iter().map(|record| format!("{record}\n")).collect::<String>();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&self.ledger_path)
+            .await
+            .context("couldn't open the ledger file")?;
+        file.write_all(lines.as_bytes()).await.context("couldn't append to the ledger file")?;
+        file.sync_data().await.context("couldn't sync the ledger file to the disk")
+    }
+
+    #[instrument]
+    async fn revert_records_after(
+        &self,
+        block_number: Option<BlockNumber>,
+    ) -> Result<Vec<LedgerRecord>> {
+        let contents =
+            read_to_string(&self.ledger_path).await.context("couldn't read the ledger file")?;
+        let mut records = parse_ledger(&contents)?;
+
+        // records are in the order of the blocks, so the reverted ones are at the end
+        let kept = records
+            .iter()
+            .position(|record| block_number.map_or(true, |number| record.block_number > number))
+            .unwrap_or(records.len());
+        let reverted = records.split_off(kept);
+        if reverted.is_empty() {
+            return Ok(reverted);
+        }
+
+        // the header comes before the kept records
+        let length = contents.split_inclusive('\n').take(kept + 1).map(str::len).sum::<usize>();
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&self.ledger_path)
+            .await
+            .context("couldn't open the ledger file")?;
+        file.set_len(length as u64).await.context("couldn't truncate the ledger file")?;
+        file.sync_all().await.context("couldn't sync the ledger file to the disk")?;
+
+        Ok(reverted)
+    }
+}
+
+/// reads and parses the summary at the given path
+async fn read_summary(path: &Path) -> Result<Summary> {
+    let contents = read_to_string(path)
+        .await
+        .wrap_err_with(|| format!("couldn't read the contents of `{}`", path.display()))?;
+    toml::from_str(&contents).context("couldn't serialize the summary content")
+}
+
+/// parses the complete records of the ledger
+///
+/// an incomplete record at the end of the ledger (i.e. while it is being
+/// appended) is ignored
+fn parse_ledger(contents: &str) -> Result<Vec<LedgerRecord>> {
+    let mut lines = contents.split_inclusive('\n').filter(|line| line.ends_with('\n'));
+    match lines.next() {
+        Some(header) if header.trim_end() == LEDGER_HEADER => (),
+        None => return Ok(vec![]),
+        Some(_) => return Err(eyre!("ledger file has an unknown header")),
+    }
+
+    lines
+        .enumerate()
+        .map(|(index, line)| {
+            // the header is the first line
+            line.trim_end()
+                .parse()
+                .wrap_err_with(|| format!("ledger record at line {} is invalid", index + 2))
+        })
+        .collect()
+}
+
+/// returns the path of the backup of the summary file
+pub(crate) fn backup_path(summary_path: &Path) -> PathBuf {
+    summary_path.with_extension("toml.bak")
+}
+
+/// returns the files of the storage in the given summary directory, whether
+/// they exist or not
+pub(crate) fn files(summary_dir: &Path) -> Vec<PathBuf> {
+    let summary_path = summary_path(summary_dir);
+    vec![backup_path(&summary_path), ledger_path(summary_dir), summary_path]
+}
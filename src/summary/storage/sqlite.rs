@@ -0,0 +1,341 @@
+//! Storage of the summary, and of its reward ledger, in `summary.db`, an
+//! SQLite database.
+//!
+//! The summary is a single row, and every record of the ledger is a row
+//! indexed by its block number and timestamp, so the records of a range of
+//! blocks or of time are queried without reading the whole ledger. The
+//! database is in the WAL mode, so `info` reads it while `farm` is writing.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Context, Result};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use subspace_sdk::node::BlockNumber;
+use tracing::instrument;
+
+use super::{db_path, RecordQuery, SummaryStorage};
+use crate::summary::ledger::LedgerRecord;
+use crate::summary::Summary;
+
+/// for how long a write waits for the other connections to the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// tables and indexes of the database
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS summary (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    summary TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS ledger (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    block_number INTEGER NOT NULL,
+    block_hash TEXT NOT NULL,
+    timestamp INTEGER,
+    kind TEXT NOT NULL,
+    -- amounts do not fit into the integers of SQLite
+    amount TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS ledger_block_number ON ledger (block_number);
+CREATE INDEX IF NOT EXISTS ledger_timestamp ON ledger (timestamp);
+";
+
+/// fields of the records, in the order of [`RawRecord`]
+const RECORD_FIELDS: &str = "block_number, block_hash, timestamp, kind, amount";
+
+/// `summary.db` in the summary directory
+#[derive(Debug, Clone)]
+pub(crate) struct SqliteStorage {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    /// opens the database in the given summary directory, creating it if it
+    /// does not exist
+    #[instrument]
+    pub(crate) async fn open(summary_dir: &Path) -> Result<SqliteStorage> {
+        SqliteStorage::open_path(db_path(summary_dir)).await
+    }
+
+    /// opens the database at the given path
+    async fn open_path(path: PathBuf) -> Result<SqliteStorage> {
+        let connection = tokio::task::spawn_blocking(move || open_connection(&path))
+            .await
+            .context("opening the summary database panicked")??;
+        Ok(SqliteStorage { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    /// creates the database in the given summary directory, with the given
+    /// summary and records
+    ///
+    /// the database is only in place once it is complete, so a crash leaves
+    /// either no database, or a complete one
+    #[instrument(skip(records))]
+    pub(crate) async fn create(
+        summary_dir: &Path,
+        summary: Option<&Summary>,
+        records: &[LedgerRecord],
+    ) -> Result<()> {
+        let path = db_path(summary_dir);
+        let temp_path = path.with_extension("db.tmp");
+        for path in db_files_of(&temp_path) {
+            let _ = std::fs::remove_file(path);
+        }
+
+        let storage = SqliteStorage::open_path(temp_path.clone()).await?;
+        let summary = summary.map(serialize_summary).transpose()?;
+        let records = records.to_vec();
+        storage
+            .with_connection(move |connection| {
+                let transaction =
+                    connection.transaction().context("couldn't start a transaction")?;
+                if let Some(summary) = summary {
+                    write_summary(&transaction, &summary)?;
+                }
+                insert_records(&transaction, &records)?;
+                transaction.commit().context("couldn't commit the summary to the database")
+            })
+            .await?;
+        // the last connection to close merges the write-ahead log into the database
+        drop(storage);
+
+        tokio::fs::rename(&temp_path, &path).await.context("couldn't move the summary database")
+    }
+
+    /// runs the given function with the connection, on a thread which may
+    /// block
+    async fn with_connection<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection =
+                connection.lock().map_err(|_| eyre!("summary database connection is poisoned"))?;
+            f(&mut connection)
+        })
+        .await
+        .context("summary database task panicked")?
+    }
+}
+
+#[async_trait]
+impl SummaryStorage for SqliteStorage {
+    async fn read(&self) -> Result<Summary> {
+        let summary = self
+            .with_connection(|connection| {
+                connection
+                    .query_row("SELECT summary FROM summary WHERE id = 0", [], |row| {
+                        row.get::<_, String>(0)
+                    })
+                    .optional()
+                    .context("couldn't read the summary from the database")
+            })
+            .await?
+            .ok_or_else(|| eyre!("there is no summary in the database"))?;
+        toml::from_str::<Summary>(&summary).context("couldn't serialize the summary content")
+    }
+
+    async fn write(&self, summary: &Summary) -> Result<()> {
+        let summary = serialize_summary(summary)?;
+        self.with_connection(move |connection| write_summary(connection, &summary)).await
+    }
+
+    /// the corrupt summary is replaced in place, as there is no backup to
+    /// keep it next to
+    async fn set_aside(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// the ledger is created along with the database, and a transaction is
+    /// never left incomplete
+    async fn open_ledger(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn records(&self, query: RecordQuery) -> Result<Vec<LedgerRecord>> {
+        let RecordQuery { from_block, to_block, from_timestamp, to_timestamp } = query;
+        let timestamp = |timestamp: u64| i64::try_from(timestamp).unwrap_or(i64::MAX);
+        // only the given bounds are in the statement, so that the indexes are used
+        let (conditions, values): (Vec<_>, Vec<_>) = [
+            ("block_number >= ?", from_block.map(i64::from)),
+            ("block_number <= ?", to_block.map(i64::from)),
+            ("timestamp >= ?", from_timestamp.map(timestamp)),
+            ("timestamp <= ?", to_timestamp.map(timestamp)),
+        ]
+        .into_iter()
+        .filter_map(|(condition, value)| Some((condition, value?)))
+        .unzip();
+        let filter = match conditions.is_empty() {
+            true => String::new(),
+            false => format!("WHERE {}", conditions.join(" AND ")),
+        };
+
+        self.with_connection(move |connection| {
+            let mut statement = connection
+                .prepare(&format!(
+                    "SELECT {RECORD_FIELDS} FROM ledger {filter} ORDER BY block_number, id"
+                ))
+                .context("couldn't prepare the query of the ledger")?;
+            let rows = statement
+                .query_map(params_from_iter(values), raw_record)
+                .context("couldn't query the ledger")?;
+            rows.map(|row| {
+                row.context("couldn't read a record of the ledger").and_then(parse_record)
+            })
+            .collect()
+        })
+        .await
+    }
+
+    async fn last_records(&self) -> Result<Vec<LedgerRecord>> {
+        self.with_connection(|connection| {
+            let mut statement = connection
+                .prepare(&format!(
+                    "SELECT {RECORD_FIELDS} FROM ledger WHERE block_number = (SELECT \
+                     MAX(block_number) FROM ledger) ORDER BY id"
+                ))
+                .context("couldn't prepare the query of the ledger")?;
+            let rows = statement.query_map([], raw_record).context("couldn't query the ledger")?;
+            rows.map(|row| {
+                row.context("couldn't read a record of the ledger").and_then(parse_record)
+            })
+            .collect()
+        })
+        .await
+    }
+
+    async fn append_records(&self, records: &[LedgerRecord]) -> Result<()> {
+        let records = records.to_vec();
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction().context("couldn't start a transaction")?;
+            insert_records(&transaction, &records)?;
+            transaction.commit().context("couldn't append to the ledger")
+        })
+        .await
+    }
+
+    #[instrument]
+    async fn revert_records_after(
+        &self,
+        block_number: Option<BlockNumber>,
+    ) -> Result<Vec<LedgerRecord>> {
+        // every record is after the genesis block, if there is none to keep
+        let after = block_number.map_or(-1, i64::from);
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction().context("couldn't start a transaction")?;
+            let reverted = {
+                let mut statement = transaction
+                    .prepare(&format!(
+                        "SELECT {RECORD_FIELDS} FROM ledger WHERE block_number > ?1 ORDER BY \
+                         block_number, id"
+                    ))
+                    .context("couldn't prepare the query of the ledger")?;
+                let rows = statement
+                    .query_map(params![after], raw_record)
+                    .context("couldn't query the ledger")?;
+                rows.map(|row| {
+                    row.context("couldn't read a record of the ledger").and_then(parse_record)
+                })
+                .collect::<Result<Vec<_>>>()?
+            };
+            transaction
+                .execute("DELETE FROM ledger WHERE block_number > ?1", params![after])
+                .context("couldn't remove the reverted records")?;
+            transaction.commit().context("couldn't revert the ledger")?;
+            Ok(reverted)
+        })
+        .await
+    }
+}
+
+/// opens the database at the given path, and creates its tables
+fn open_connection(path: &Path) -> Result<Connection> {
+    let connection = Connection::open(path)
+        .wrap_err_with(|| format!("couldn't open the summary database `{}`", path.display()))?;
+    connection.busy_timeout(BUSY_TIMEOUT).context("couldn't set the busy timeout")?;
+    // readers and the writer do not block each other in the WAL mode
+    connection
+        .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+        .context("couldn't switch the summary database to the WAL mode")?;
+    connection.execute_batch(SCHEMA).context("couldn't create the tables of the summary")?;
+    Ok(connection)
+}
+
+/// serializes the summary, the same way as in `summary.toml`
+fn serialize_summary(summary: &Summary) -> Result<String> {
+    toml::to_string(summary).context("Failed to serialize Summary")
+}
+
+/// replaces the summary in the database
+fn write_summary(connection: &Connection, summary: &str) -> Result<()> {
+    connection
+        .execute("INSERT OR REPLACE INTO summary (id, summary) VALUES (0, ?1)", params![summary])
+        .context("couldn't write the summary to the database")
+        .map(drop)
+}
+
+/// inserts the given records into the ledger
+fn insert_records(connection: &Connection, records: &[LedgerRecord]) -> Result<()> {
+    let mut statement = connection
+        .prepare(&format!("INSERT INTO ledger ({RECORD_FIELDS}) VALUES (?1, ?2, ?3, ?4, ?5)"))
+        .context("couldn't prepare the insertion into the ledger")?;
+    for LedgerRecord { block_number, block_hash, timestamp, kind, amount } in records {
+        let timestamp = timestamp
+            .map(i64::try_from)
+            .transpose()
+            .context("timestamp of the record is too large")?;
+        statement
+            .execute(params![
+                block_number,
+                block_hash,
+                timestamp,
+                kind.to_string(),
+                amount.to_string()
+            ])
+            .context("couldn't append to the ledger")?;
+    }
+    Ok(())
+}
+
+/// fields of a record of the ledger, as they are in the database
+type RawRecord = (BlockNumber, String, Option<i64>, String, String);
+
+/// reads the fields of a record of the ledger from the given row
+fn raw_record(row: &rusqlite::Row<'_>) -> rusqlite::Result<RawRecord> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+}
+
+/// parses the fields of a record of the ledger
+fn parse_record(
+    (block_number, block_hash, timestamp, kind, amount): RawRecord,
+) -> Result<LedgerRecord> {
+    Ok(LedgerRecord {
+        block_number,
+        block_hash,
+        timestamp: timestamp.map(u64::try_from).transpose().context("invalid timestamp")?,
+        kind: kind.parse().wrap_err_with(|| format!("unknown kind `{kind}`"))?,
+        amount: amount.parse().context("invalid amount")?,
+    })
+}
+
+/// returns the files of the database in the given summary directory, whether
+/// they exist or not
+pub(crate) fn db_files(summary_dir: &Path) -> Vec<PathBuf> {
+    db_files_of(&db_path(summary_dir))
+}
+
+/// returns the database at the given path, along with its write-ahead log
+fn db_files_of(path: &Path) -> Vec<PathBuf> {
+    ["", "-wal", "-shm"]
+        .into_iter()
+        .map(|suffix| {
+            let mut path = path.as_os_str().to_owned();
+            path.push(suffix);
+            path.into()
+        })
+        .collect()
+}
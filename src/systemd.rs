@@ -0,0 +1,111 @@
+//! Notifications of `farm` to systemd.
+//!
+//! Under a `Type=notify` unit (i.e. one written by `service install`), `farm`
+//! tells systemd that it is ready once the node is synced and the farmer is
+//! built, pings the watchdog of the unit, and keeps the status shown by
+//! `systemctl status` up to date. Outside of systemd, or on other OS than
+//! Linux, the notifications are dropped.
+
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use sd_notify::NotifyState;
+
+use crate::status::{Status, StatusReport};
+use crate::summary::Units;
+
+/// interval of the heartbeats, if the unit has no watchdog
+const STATUS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// notifications of the service manager, only supported on Linux
+#[cfg(not(target_os = "linux"))]
+enum NotifyState<'a> {
+    Ready,
+    Stopping,
+    Watchdog,
+    Status(&'a str),
+}
+
+/// sends the given notifications to systemd, if `farm` runs under it
+#[cfg(target_os = "linux")]
+fn notify(states: &[NotifyState<'_>]) {
+    if let Err(err) = sd_notify::notify(false, states) {
+        tracing::warn!("couldn't notify systemd: {err}");
+    }
+}
+
+/// systemd is only supported on Linux
+#[cfg(not(target_os = "linux"))]
+fn notify(_states: &[NotifyState<'_>]) {}
+
+/// returns the interval of the heartbeats, half of the watchdog timeout of the
+/// unit if it has one
+pub(crate) fn heartbeat_interval() -> Duration {
+    #[cfg(target_os = "linux")]
+    {
+        let mut watchdog_usec = 0;
+        if sd_notify::watchdog_enabled(false, &mut watchdog_usec) {
+            return Duration::from_micros(watchdog_usec / 2).max(Duration::from_secs(1));
+        }
+    }
+    STATUS_INTERVAL
+}
+
+/// notifies that the farm is ready, once the node is synced and the farmer is
+/// built
+pub(crate) fn notify_ready(status: &Status) {
+    let line = status_line(&status.report());
+    notify(&[NotifyState::Ready, NotifyState::Status(&line)]);
+}
+
+/// pings the watchdog, along with the current status of the farm
+pub(crate) fn notify_alive(status: &Status) {
+    let line = status_line(&status.report());
+    notify(&[NotifyState::Watchdog, NotifyState::Status(&line)]);
+}
+
+/// notifies that the farm is shutting down
+pub(crate) fn notify_stopping() {
+    notify(&[NotifyState::Stopping, NotifyState::Status("Shutting down")]);
+}
+
+/// pings the watchdog on every heartbeat, while the node syncs and the farmer
+/// is built
+pub(crate) async fn keep_alive(status: Status) {
+    let mut heartbeat = tokio::time::interval(heartbeat_interval());
+    loop {
+        heartbeat.tick().await;
+        notify_alive(&status);
+    }
+}
+
+/// returns a line of the current syncing or plotting progress, or of the
+/// rewards once the farm is farming
+pub(crate) fn status_line(report: &StatusReport) -> String {
+    let StatusReport { is_farmer_built, node, plots, summary, .. } = report;
+    if !node.is_synced {
+        return match (node.sync_position, node.sync_target) {
+            (Some(position), Some(target)) => format!("Syncing: #{position} of #{target}"),
+            _ => "Syncing".to_owned(),
+        };
+    }
+    if !is_farmer_built {
+        return "Starting the farmer".to_owned();
+    }
+
+    let plotted = plots.iter().map(|plot| plot.plotted_bytes).sum::<u64>();
+    let allocated = plots.iter().map(|plot| plot.allocated_bytes).sum::<u64>();
+    if plotted < allocated {
+        return format!("Plotting: {:.2}%", plotted as f64 / allocated as f64 * 100.0);
+    }
+
+    match summary {
+        Some(summary) => format!(
+            "Farming: {} earned, farmed {} block(s), and voted on {} block(s)",
+            summary.token.clone().unwrap_or_default().format(summary.total_rewards.0, Units::Ssc),
+            summary.authored_count,
+            summary.vote_count,
+        ),
+        None => "Farming".to_owned(),
+    }
+}
@@ -1,14 +1,38 @@
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 use subspace_sdk::ByteSize;
 
-use crate::config::ChainConfig;
+use crate::commands::export::{bound_parser, record_query, reward_row, Bound};
+use crate::commands::farm::find_fork_point;
+use crate::commands::service::{farm_args, render_unit, unit_name, unit_user};
+use crate::commands::wipe::find_plot;
+use crate::config::keys::{get_value, parse_value, set_value};
+use crate::config::layers::{apply_layers, env_overrides, ConfigSource};
+use crate::config::migration::{config_version, migrate, CONFIG_VERSION};
+use crate::config::preflight::{preflight, unknown_keys, unknown_options};
+use crate::config::{
+    replace_config_file, ChainConfig, Config, CustomChainConfig, MetricsConfig, PlotConfig,
+    CONFIG_FILE,
+};
+use crate::control::{
+    listen, send_request, Control, ControlRequest, ControlResponse, PAUSE_PLOTTING_UNSUPPORTED,
+};
+use crate::metrics::Metrics;
+use crate::output::{FarmEvent, OutputFormat};
+use crate::profile::{profile_name_parser, profile_names, Profile, DEFAULT_PROFILE};
+use crate::status::{Health, PlotStatus, Status};
+use crate::summary::history::{history, sparkline, Period};
+use crate::summary::ledger::*;
+use crate::summary::storage::{db_path, migrate_storage, RecordQuery, StorageKind};
 use crate::summary::*;
+use crate::systemd::status_line;
 use crate::utils::{
-    apply_extra_options, cache_directory_getter, custom_log_dir, directory_parser,
-    node_directory_getter, node_name_parser, plot_directory_getter, reward_address_parser,
+    apply_extra_options, cache_directory_getter, custom_log_dir, date_parser, directory_parser,
+    existing_directory_parser, format_amount, key_value_parser, node_directory_getter,
+    node_name_parser, plot_directory_getter, provider_storage_dir_getter, reward_address_parser,
     size_parser, yes_or_no_parser,
 };
 
@@ -21,7 +45,9 @@ async fn update_summary_file_randomly(summary_file: SummaryFile) {
             new_authored_count: rng.gen_range(1..10),
             new_vote_count: rng.gen_range(1..10),
             new_reward: Rewards(rng.gen_range(1..1000)),
-            new_parsed_blocks: rng.gen_range(1..100),
+            last_processed_block_num: Some(rng.gen_range(1..100)),
+            last_processed_block: None,
+            processed_blocks: vec![],
         };
         let result = summary_file.update(update_fields).await;
         assert!(result.is_ok(), "Failed to update summary file");
@@ -33,14 +59,15 @@ async fn summary_file_integration() {
     // this test is mainly for CI, in which, summary file won't exist
     // if there is a summary file (user env), we don't want to modify the existing
     // summary file for test
-    if SummaryFile::new(None).await.is_ok() {
+    if SummaryFile::new(&summary_dir(), None).await.is_ok() {
         return;
     }
 
     // create summary file
     let plot_size = ByteSize::gb(1);
-    let summary_file =
-        SummaryFile::new(Some(plot_size)).await.expect("Failed to create summary file");
+    let summary_file = SummaryFile::new(&summary_dir(), Some(plot_size))
+        .await
+        .expect("Failed to create summary file");
 
     // sequential update trial
     let update_fields = SummaryUpdateFields {
@@ -48,7 +75,9 @@ async fn summary_file_integration() {
         new_authored_count: 11,
         new_vote_count: 11,
         new_reward: Rewards(1001),
-        new_parsed_blocks: 101,
+        last_processed_block_num: Some(101),
+        last_processed_block: None,
+        processed_blocks: vec![],
     };
     summary_file.update(update_fields).await.expect("Failed to update summary file");
 
@@ -66,7 +95,480 @@ async fn summary_file_integration() {
     summary_file.parse().await.expect("Failed to parse the summary file after updates");
 
     // Clean up the summary file
-    delete_summary().expect("summary deletion failed");
+    delete_summary(&summary_dir()).expect("summary deletion failed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn summary_file_recovery() {
+    let summary_dir =
+        std::env::temp_dir().join(format!("subspace-cli-summary-{}", rand::random::<u64>()));
+    let summary_path = summary_path(&summary_dir);
+    let backup_path = summary_dir.join("summary.toml.bak");
+
+    let summary_file = SummaryFile::new(&summary_dir, Some(ByteSize::gb(1))).await.unwrap();
+    let update_fields = || SummaryUpdateFields { new_vote_count: 1, ..Default::default() };
+    summary_file.update(update_fields()).await.unwrap();
+    summary_file.update(update_fields()).await.unwrap();
+    assert!(backup_path.exists());
+    assert!(!summary_dir.join("summary.toml.tmp").exists());
+
+    // a crash while writing the summary leaves it empty
+    std::fs::write(&summary_path, "").unwrap();
+    assert_eq!(summary_file.parse().await.unwrap().vote_count, 1);
+    assert!(SummaryFile::new(&summary_dir, None).await.is_ok());
+
+    // the corrupt summary does not overwrite the backup
+    assert_eq!(summary_file.update(update_fields()).await.unwrap().vote_count, 2);
+    assert_eq!(summary_file.parse().await.unwrap().vote_count, 2);
+
+    // if the backup is corrupt as well, a new summary is only started by `farm`
+    std::fs::write(&summary_path, "").unwrap();
+    std::fs::write(&backup_path, "").unwrap();
+    assert!(SummaryFile::new(&summary_dir, None).await.is_err());
+    let summary_file = SummaryFile::new(&summary_dir, Some(ByteSize::gb(1))).await.unwrap();
+    assert_eq!(summary_file.parse().await.unwrap().vote_count, 0);
+    assert!(summary_dir.join("summary.toml.corrupt").exists());
+
+    std::fs::remove_dir_all(&summary_dir).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reward_ledger() {
+    let summary_dir =
+        std::env::temp_dir().join(format!("subspace-cli-ledger-{}", rand::random::<u64>()));
+    let record = |block_number, kind, amount| LedgerRecord {
+        block_number,
+        block_hash: format!("0x{block_number:064x}"),
+        timestamp: Some(1_680_000_000 + block_number as u64),
+        kind,
+        amount,
+    };
+    let first_batch = vec![
+        record(7, RewardKind::Vote, 0),
+        record(3, RewardKind::BlockReward, 100),
+        record(7, RewardKind::VoteReward, 10),
+    ];
+    let second_batch = vec![record(9, RewardKind::Vote, 0), record(9, RewardKind::Vote, 0)];
+
+    let ledger = Ledger::open(&summary_dir).await.unwrap();
+    ledger.append(first_batch.clone()).await.unwrap();
+    // a batch processed again after a crash is not recorded twice
+    ledger.append(first_batch.clone()).await.unwrap();
+    let records = read_ledger(&summary_dir).await.unwrap();
+    assert_eq!(records.iter().map(|record| record.block_number).collect::<Vec<_>>(), [3, 7, 7]);
+    assert_eq!(records[0], record(3, RewardKind::BlockReward, 100));
+
+    // a crash while appending leaves an incomplete record, which is dropped
+    let ledger_path = ledger_path(&summary_dir);
+    let mut contents = std::fs::read_to_string(&ledger_path).unwrap();
+    contents.push_str(&format!("{}\n", record(9, RewardKind::Vote, 0)));
+    contents.push_str("9,0x");
+    std::fs::write(&ledger_path, contents).unwrap();
+    assert_eq!(read_ledger(&summary_dir).await.unwrap().len(), 4);
+
+    let ledger = Ledger::open(&summary_dir).await.unwrap();
+    ledger.append(second_batch).await.unwrap();
+    let records = read_ledger(&summary_dir).await.unwrap();
+    assert_eq!(records.len(), 5);
+
+    let totals = LedgerTotals::from_records(&records);
+    assert_eq!(totals, LedgerTotals { authored_count: 1, vote_count: 3, total_rewards: 110 });
+    let summary = Summary {
+        authored_count: 1,
+        vote_count: 3,
+        total_rewards: Rewards(110),
+        ..Default::default()
+    };
+    assert!(totals.check(&summary).is_ok());
+    // authored blocks without a reward record are not a mismatch
+    assert!(totals.check(&Summary { authored_count: 2, ..summary.clone() }).is_ok());
+    let err = totals.check(&Summary { vote_count: 2, ..summary }).unwrap_err();
+    assert!(err.to_string().contains("vote(s): 3 in the ledger, 2 in the summary"));
+
+    std::fs::remove_dir_all(&summary_dir).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sqlite_storage() {
+    let summaries_dir =
+        std::env::temp_dir().join(format!("subspace-cli-sqlite-{}", rand::random::<u64>()));
+    let record = |block_number, kind, amount| LedgerRecord {
+        block_number,
+        block_hash: format!("0x{block_number:064x}"),
+        timestamp: (block_number != 5).then_some(1_680_000_000 + block_number as u64),
+        kind,
+        amount,
+    };
+    let records = vec![
+        record(3, RewardKind::BlockReward, 100),
+        record(5, RewardKind::Vote, 0),
+        record(5, RewardKind::VoteReward, u128::MAX),
+        record(7, RewardKind::BlockReward, 100),
+    ];
+
+    // an existing summary is migrated along with its ledger
+    let summary_dir = summaries_dir.join("migrated");
+    let summary_file = SummaryFile::new(&summary_dir, Some(ByteSize::gb(1))).await.unwrap();
+    summary_file
+        .update(SummaryUpdateFields { new_vote_count: 1, ..Default::default() })
+        .await
+        .unwrap();
+    Ledger::open(&summary_dir).await.unwrap().append(records[..2].to_vec()).await.unwrap();
+    migrate_storage(&summary_dir, StorageKind::Toml).await.unwrap();
+    assert!(!db_path(&summary_dir).exists());
+    migrate_storage(&summary_dir, StorageKind::Sqlite).await.unwrap();
+    assert!(db_path(&summary_dir).exists());
+    assert!(!summary_path(&summary_dir).exists());
+    assert!(summary_dir.join("summary.toml.migrated").exists());
+    assert!(summary_dir.join("ledger.csv.migrated").exists());
+    assert_eq!(
+        SummaryFile::new(&summary_dir, None).await.unwrap().parse().await.unwrap().vote_count,
+        1
+    );
+    assert_eq!(read_ledger(&summary_dir).await.unwrap(), records[..2]);
+    // and it stays in the database
+    migrate_storage(&summary_dir, StorageKind::Toml).await.unwrap();
+    assert!(db_path(&summary_dir).exists());
+
+    // the ledger works the same way in the database
+    let ledger = Ledger::open(&summary_dir).await.unwrap();
+    ledger.append(records.clone()).await.unwrap();
+    assert_eq!(ledger.records().await.unwrap(), records);
+    let query = RecordQuery { from_block: Some(4), to_block: Some(6), ..Default::default() };
+    assert_eq!(query_ledger(&summary_dir, query).await.unwrap(), records[1..3]);
+    // records without a timestamp are not in any range of time
+    let query = RecordQuery { from_timestamp: Some(1_680_000_004), ..Default::default() };
+    assert_eq!(query_ledger(&summary_dir, query).await.unwrap(), records[3..]);
+    assert_eq!(ledger.revert_after(Some(4)).await.unwrap(), records[1..]);
+    ledger.append(records[3..].to_vec()).await.unwrap();
+    assert_eq!(read_ledger(&summary_dir).await.unwrap(), [records[0].clone(), records[3].clone()]);
+
+    // a new summary starts in the database
+    let new_dir = summaries_dir.join("new");
+    migrate_storage(&new_dir, StorageKind::Sqlite).await.unwrap();
+    assert!(SummaryFile::new(&new_dir, None).await.is_err());
+    let summary_file = SummaryFile::new(&new_dir, Some(ByteSize::gb(1))).await.unwrap();
+    assert_eq!(summary_file.parse().await.unwrap().user_space_pledged, ByteSize::gb(1));
+    assert!(read_ledger(&new_dir).await.unwrap().is_empty());
+
+    for summary_dir in [&summary_dir, &new_dir] {
+        delete_summary(summary_dir).unwrap();
+        assert_eq!(std::fs::read_dir(summary_dir).unwrap().count(), 0);
+    }
+    std::fs::remove_dir_all(&summaries_dir).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reorg_without_rewards() {
+    let summary_dir =
+        std::env::temp_dir().join(format!("subspace-cli-fork-{}", rand::random::<u64>()));
+    let block = |number, fork: &str| ProcessedBlock { number, hash: format!("0x{fork}{number}") };
+
+    // a farmer without any reward processes blocks #0 to #299, in batches
+    let summary_file = SummaryFile::new(&summary_dir, Some(ByteSize::gb(1))).await.unwrap();
+    for batch in (0..300).collect::<Vec<_>>().chunks(100) {
+        let processed_blocks = batch.iter().map(|number| block(*number, "a")).collect::<Vec<_>>();
+        summary_file
+            .update(SummaryUpdateFields {
+                last_processed_block_num: Some(batch[batch.len() - 1] + 1),
+                last_processed_block: processed_blocks.last().cloned(),
+                processed_blocks,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+    let summary = summary_file.parse().await.unwrap();
+    assert_eq!(summary.recent_blocks.len(), RECENT_BLOCKS);
+    assert_eq!(summary.recent_blocks.first(), Some(&block(44, "a")));
+
+    // the chain forked after the block #295, which is found without the ledger
+    let mut checked = vec![];
+    let fork_point = find_fork_point(&summary, &[], |processed| {
+        checked.push(processed.number);
+        Ok(processed.number <= 295)
+    })
+    .unwrap();
+    assert_eq!(fork_point, Some(block(295, "a")));
+    assert_eq!(checked, [298, 297, 296, 295]);
+
+    let summary = summary_file.revert(LedgerTotals::default(), fork_point).await.unwrap();
+    assert_eq!(summary.last_processed_block_num, 296);
+    assert_eq!(summary.recent_blocks.last(), Some(&block(295, "a")));
+
+    // a reorg deeper than the recent blocks falls back to the ledger
+    let records = [LedgerRecord {
+        block_number: 10,
+        block_hash: block(10, "a").hash,
+        timestamp: None,
+        kind: RewardKind::Vote,
+        amount: 0,
+    }];
+    let fork_point = find_fork_point(&summary, &records, |processed| Ok(processed.number <= 20));
+    assert_eq!(fork_point.unwrap(), Some(block(10, "a")));
+    let fork_point = find_fork_point(&summary, &[], |processed| Ok(processed.number <= 20));
+    assert_eq!(fork_point.unwrap(), None);
+
+    std::fs::remove_dir_all(&summary_dir).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ledger_reorg() {
+    let summary_dir =
+        std::env::temp_dir().join(format!("subspace-cli-reorg-{}", rand::random::<u64>()));
+    let record = |block_number, kind, amount| LedgerRecord {
+        block_number,
+        block_hash: format!("0x{block_number:064x}"),
+        timestamp: None,
+        kind,
+        amount,
+    };
+
+    let summary_file = SummaryFile::new(&summary_dir, Some(ByteSize::gb(1))).await.unwrap();
+    let ledger = Ledger::open(&summary_dir).await.unwrap();
+    let records = vec![
+        record(3, RewardKind::BlockReward, 100),
+        record(7, RewardKind::Vote, 0),
+        record(7, RewardKind::VoteReward, 10),
+        record(9, RewardKind::BlockReward, 100),
+    ];
+    let totals = LedgerTotals::from_records(&records);
+    ledger.append(records).await.unwrap();
+    summary_file
+        .update(SummaryUpdateFields {
+            new_authored_count: totals.authored_count,
+            new_vote_count: totals.vote_count,
+            new_reward: Rewards(totals.total_rewards),
+            last_processed_block_num: Some(11),
+            last_processed_block: Some(ProcessedBlock { number: 10, hash: "0x0a".to_owned() }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // the blocks after #3 are reverted
+    let reverted = ledger.revert_after(Some(3)).await.unwrap();
+    assert_eq!(reverted.len(), 3);
+    let fork_point = ProcessedBlock { number: 3, hash: format!("0x{:064x}", 3) };
+    let summary = summary_file
+        .revert(LedgerTotals::from_records(&reverted), Some(fork_point.clone()))
+        .await
+        .unwrap();
+    assert_eq!(summary.last_processed_block_num, 4);
+    assert_eq!(summary.last_processed_block, Some(fork_point));
+    let records = read_ledger(&summary_dir).await.unwrap();
+    assert_eq!(records, [record(3, RewardKind::BlockReward, 100)]);
+    assert!(LedgerTotals::from_records(&records).check(&summary).is_ok());
+
+    // the records of the blocks processed again are appended after the kept ones
+    ledger.append(vec![record(5, RewardKind::Vote, 0)]).await.unwrap();
+    assert_eq!(read_ledger(&summary_dir).await.unwrap().len(), 2);
+
+    std::fs::remove_dir_all(&summary_dir).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scoped_summaries() {
+    let summaries_dir =
+        std::env::temp_dir().join(format!("subspace-cli-summaries-{}", rand::random::<u64>()));
+    let key = |chain: &str, reward_address: &str| SummaryKey {
+        chain: chain.to_owned(),
+        genesis_hash: format!("0x{:064x}", 42),
+        reward_address: reward_address.to_owned(),
+    };
+
+    // the summary of the older versions is not moved to another key
+    let unscoped_summary = SummaryFile::new(&summaries_dir, Some(ByteSize::gb(1))).await.unwrap();
+    unscoped_summary
+        .update(SummaryUpdateFields { new_vote_count: 1, ..Default::default() })
+        .await
+        .unwrap();
+    Ledger::open(&summaries_dir).await.unwrap();
+    assert_eq!(active_summary_dir(&summaries_dir), summaries_dir);
+
+    let gemini_dir =
+        activate_summary(&summaries_dir, &key("Subspace Gemini 3d", "st1"), false).unwrap();
+    let legacy_dir = summaries_dir.join("summaries").join("legacy");
+    assert!(!summary_path(&summaries_dir).exists());
+    assert!(summary_path(&legacy_dir).exists());
+    let gemini_summary = SummaryFile::new(&gemini_dir, Some(ByteSize::gb(1))).await.unwrap();
+    assert_eq!(gemini_summary.parse().await.unwrap().vote_count, 0);
+
+    // but to the key of the config the older version farmed with
+    let devnet_dir =
+        activate_summary(&summaries_dir, &key("Subspace Dev Net", "st1"), true).unwrap();
+    assert!(!legacy_dir.exists());
+    assert_eq!(devnet_dir.file_name().unwrap(), "subspace-dev-net_0000000000000000_st1");
+    assert_eq!(active_summary_dir(&summaries_dir), devnet_dir);
+    assert!(!summary_path(&summaries_dir).exists());
+    assert!(ledger_path(&devnet_dir).exists());
+    let devnet_summary = SummaryFile::new(&devnet_dir, None).await.unwrap();
+    assert_eq!(devnet_summary.parse().await.unwrap().vote_count, 1);
+    devnet_summary.set_key(key("Subspace Dev Net", "st1"), Token::default()).await.unwrap();
+
+    // another chain or reward address starts from scratch
+    let gemini_dir =
+        activate_summary(&summaries_dir, &key("Subspace Gemini 3d", "st1"), false).unwrap();
+    let gemini_summary = SummaryFile::new(&gemini_dir, Some(ByteSize::gb(1))).await.unwrap();
+    assert_eq!(gemini_summary.parse().await.unwrap().vote_count, 0);
+    let reward_address_dir =
+        activate_summary(&summaries_dir, &key("Subspace Gemini 3d", "st2"), false);
+    assert_ne!(reward_address_dir.unwrap(), gemini_dir);
+
+    // and switching back continues the old summary
+    assert_eq!(
+        activate_summary(&summaries_dir, &key("Subspace Dev Net", "st1"), false).unwrap(),
+        devnet_dir
+    );
+    assert_eq!(summary_dirs(&summaries_dir).unwrap(), [devnet_dir.clone(), gemini_dir]);
+    assert_eq!(
+        SummaryFile::new(&devnet_dir, None).await.unwrap().parse().await.unwrap().key,
+        Some(key("Subspace Dev Net", "st1"))
+    );
+
+    // an unscoped summary which records its key is only moved to that key
+    let unscoped_summary = SummaryFile::new(&summaries_dir, Some(ByteSize::gb(1))).await.unwrap();
+    unscoped_summary.set_key(key("Subspace Dev Net", "st3"), Token::default()).await.unwrap();
+    activate_summary(&summaries_dir, &key("Subspace Dev Net", "st2"), true).unwrap();
+    assert!(summary_path(&legacy_dir).exists());
+    let recorded_dir =
+        activate_summary(&summaries_dir, &key("Subspace Dev Net", "st3"), false).unwrap();
+    assert!(summary_path(&recorded_dir).exists());
+    assert!(!legacy_dir.exists());
+
+    delete_summaries(&summaries_dir).unwrap();
+    assert!(summary_dirs(&summaries_dir).unwrap().is_empty());
+    assert_eq!(active_summary_dir(&summaries_dir), summaries_dir);
+    assert!(delete_summaries(&summaries_dir).is_err());
+
+    std::fs::remove_dir_all(&summaries_dir).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn summary_lock() {
+    use std::time::Duration;
+
+    use crate::summary::lock::{LockKind, SummaryLock};
+
+    let summary_dir =
+        std::env::temp_dir().join(format!("subspace-cli-lock-{}", rand::random::<u64>()));
+    let summary_file = SummaryFile::new(&summary_dir, Some(ByteSize::gb(1))).await.unwrap();
+    let path = summary_path(&summary_dir);
+
+    // readers do not block each other
+    let reader = SummaryLock::acquire(&path, LockKind::Shared).await.unwrap();
+    summary_file.parse().await.unwrap();
+
+    // but a writer waits for them, i.e. for `info` of another process
+    let update = tokio::spawn({
+        let summary_file = summary_file.clone();
+        async move {
+            summary_file
+                .update(SummaryUpdateFields { new_vote_count: 1, ..Default::default() })
+                .await
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!update.is_finished());
+    drop(reader);
+    update.await.unwrap().unwrap();
+
+    // and readers wait for a writer
+    let writer = SummaryLock::acquire(&path, LockKind::Exclusive).await.unwrap();
+    let read = tokio::spawn({
+        let summary_file = summary_file.clone();
+        async move { summary_file.parse().await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!read.is_finished());
+    drop(writer);
+    assert_eq!(read.await.unwrap().unwrap().vote_count, 1);
+
+    delete_summary(&summary_dir).unwrap();
+    std::fs::remove_dir_all(&summary_dir).unwrap();
+}
+
+#[test]
+fn reward_history() {
+    let date = |date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+    let record = |date_str, kind, amount| LedgerRecord {
+        block_number: 1,
+        block_hash: "0x00".to_owned(),
+        timestamp: Some(date(date_str).and_hms_opt(12, 0, 0).unwrap().timestamp() as u64),
+        kind,
+        amount,
+    };
+    let records = vec![
+        record("2023-03-30", RewardKind::BlockReward, 100),
+        record("2023-04-02", RewardKind::Vote, 0),
+        record("2023-04-02", RewardKind::VoteReward, 10),
+        record("2023-04-12", RewardKind::BlockReward, 100),
+        LedgerRecord { timestamp: None, ..record("2023-04-12", RewardKind::Vote, 0) },
+    ];
+
+    let by_week = history(&records, Period::Week, None, date("2023-04-20"));
+    let labels = by_week.iter().map(|(start, _)| Period::Week.label(*start)).collect::<Vec<_>>();
+    // the week without rewards is shown as well
+    assert_eq!(labels, ["2023-W13", "2023-W14", "2023-W15", "2023-W16"]);
+    let rewards = by_week.iter().map(|(_, totals)| totals.total_rewards).collect::<Vec<_>>();
+    assert_eq!(rewards, [110, 0, 100, 0]);
+    assert_eq!(by_week[0].1, LedgerTotals { authored_count: 1, vote_count: 1, total_rewards: 110 });
+    assert_eq!(sparkline(&rewards), "█▁▇▁");
+
+    let by_month = history(&records, Period::Month, Some(date("2023-04-01")), date("2023-04-20"));
+    assert_eq!(by_month.len(), 1);
+    assert_eq!(Period::Month.label(by_month[0].0), "2023-04");
+    assert_eq!(by_month[0].1.total_rewards, 110);
+
+    assert!(history(&[], Period::Day, None, date("2023-04-20")).is_empty());
+    assert_eq!(history(&[], Period::Day, Some(date("2023-04-19")), date("2023-04-20")).len(), 2);
+    assert!(date_parser("2023-04-31").is_err());
+}
+
+#[test]
+fn export_bounds_and_amounts() {
+    assert_eq!(bound_parser("1234").unwrap(), Bound::Block(1234));
+    assert_eq!(
+        bound_parser("2023-04-02").unwrap(),
+        Bound::Date(chrono::NaiveDate::from_ymd_opt(2023, 4, 2).unwrap())
+    );
+    assert!(bound_parser("99999999999").is_err());
+    assert!(bound_parser("yesterday").is_err());
+
+    let date = |day| Bound::Date(chrono::NaiveDate::from_ymd_opt(2023, 4, day).unwrap());
+    let query = record_query(Some(date(2)), Some(date(2)));
+    assert_eq!(query.from_timestamp, Some(1_680_393_600));
+    assert_eq!(query.to_timestamp, Some(1_680_479_999));
+    assert_eq!(record_query(Some(Bound::Block(5)), None).from_block, Some(5));
+}
+
+#[test]
+fn token_amounts() {
+    assert_eq!(format_amount(0, 18), "0");
+    assert_eq!(format_amount(2_000_000_000_000_000_000, 18), "2");
+    assert_eq!(format_amount(1_500_000_000_000_000_001, 18), "1.500000000000000001");
+    assert_eq!(format_amount(100_000_000_000_000, 18), "0.0001");
+    assert_eq!(format_amount(u128::MAX, 18), "340282366920938463463.374607431768211455");
+    assert_eq!(format_amount(1234, 0), "1234");
+    assert_eq!(format_amount(15, 40), "0.0000000000000000000000000000000000000015");
+
+    let token = Token { decimals: 6, symbol: "tSSC".to_owned() };
+    assert_eq!(token.format(1_250_000, Units::Ssc), "1.25 tSSC");
+    assert_eq!(token.format(1_250_000, Units::Base), "1250000 base units");
+    assert_eq!(Token::default().format(10u128.pow(18), Units::Ssc), "1 SSC");
+
+    // the export has the exact amount, along with the amount in whole tokens
+    let record = LedgerRecord {
+        block_number: 7,
+        block_hash: "0x07".to_owned(),
+        timestamp: Some(1_680_000_000),
+        kind: RewardKind::VoteReward,
+        amount: 1_250_000,
+    };
+    assert_eq!(
+        reward_row(Some("st1".to_owned()), &token, record).to_csv(),
+        format!("st1,7,0x07,2023-03-28T10:40:00Z,{},1250000,1.25,tSSC", RewardKind::VoteReward)
+    );
 }
 
 #[test]
@@ -99,6 +601,437 @@ fn extra_options() {
     assert_eq!(apply_extra_options(&cargo_toml, extra).unwrap(), result);
 }
 
+#[test]
+fn multi_plot_config() {
+    let config: Config = toml::from_str(
+        r#"
+        version = 1
+        chain = "Gemini3d"
+
+        [farmer]
+        reward_address = "5FWr7j9DW4uy7K1JLmFN2R3eoae35PFDUfW7G42ARpBEUaN7"
+
+        [[farmer.plots]]
+        directory = "/mnt/disk1"
+        size = "2 TB"
+
+        [[farmer.plots]]
+        directory = "/mnt/disk2"
+        size = "500 GB"
+
+        [node]
+        directory = "/mnt/node"
+        name = "farmer"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.farmer.plots,
+        vec![
+            PlotConfig { directory: PathBuf::from("/mnt/disk1"), size: ByteSize::tb(2) },
+            PlotConfig { directory: PathBuf::from("/mnt/disk2"), size: ByteSize::gb(500) },
+        ]
+    );
+    assert_eq!(config.farmer.total_plot_size(), ByteSize::gb(2500));
+
+    // locations which are not in the config use the defaults
+    assert_eq!(config.farmer.cache_directory, cache_directory_getter());
+    assert_eq!(config.farmer.summary_directory, summary_dir());
+    assert_eq!(config.node.provider_storage_directory, provider_storage_dir_getter());
+}
+
+#[test]
+fn replace_config() {
+    let dir = std::env::temp_dir().join(format!("subspace-cli-config-{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config_path = dir.join(CONFIG_FILE);
+    std::fs::write(&config_path, "version = 0").unwrap();
+
+    replace_config_file(&config_path, "version = 1").unwrap();
+    assert_eq!(std::fs::read_to_string(&config_path).unwrap(), "version = 1");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn wipe_plot_spelling() {
+    let dir = std::env::temp_dir().join(format!("subspace-cli-wipe-{}", rand::random::<u64>()));
+    std::fs::create_dir_all(dir.join("disk1")).unwrap();
+    let plots = vec![
+        PlotConfig { directory: dir.join("disk1"), size: ByteSize::gb(1) },
+        PlotConfig { directory: dir.join("disk2"), size: ByteSize::gb(1) },
+    ];
+
+    // the plots are found however their directory is spelled
+    assert_eq!(find_plot(&plots, &dir.join("./disk1")), Some(&plots[0]));
+    assert_eq!(find_plot(&plots, &dir.join("disk1/../disk2")), Some(&plots[1]));
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(dir.join("disk1"), dir.join("link")).unwrap();
+        assert_eq!(find_plot(&plots, &dir.join("link")), Some(&plots[0]));
+    }
+    assert_eq!(find_plot(&plots, &dir.join("disk3")), None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn configured_locations() {
+    let config: Config = toml::from_str(
+        r#"
+        version = 1
+        chain = "Gemini3d"
+
+        [farmer]
+        reward_address = "5FWr7j9DW4uy7K1JLmFN2R3eoae35PFDUfW7G42ARpBEUaN7"
+        plots = [{ directory = "/mnt/disk1", size = "2 TB" }]
+        cache_directory = "/mnt/disk1/cache"
+        summary_directory = "/mnt/disk1/summary"
+
+        [node]
+        directory = "/mnt/node"
+        name = "farmer"
+        provider_storage_directory = "/mnt/disk2/provider-storage"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.farmer.cache_directory, PathBuf::from("/mnt/disk1/cache"));
+    assert_eq!(config.farmer.summary_directory, PathBuf::from("/mnt/disk1/summary"));
+    assert_eq!(
+        config.node.provider_storage_directory,
+        PathBuf::from("/mnt/disk2/provider-storage")
+    );
+}
+
+#[test]
+fn preflight_reports_all_problems() {
+    let root =
+        std::env::temp_dir().join(format!("subspace-cli-preflight-{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&root).unwrap();
+    let config: Config = toml::from_str(&format!(
+        r#"
+        version = 1
+        chain = "Gemini3d"
+
+        [farmer]
+        reward_address = "5FWr7j9DW4uy7K1JLmFN2R3eoae35PFDUfW7G42ARpBEUaN7"
+
+        [[farmer.plots]]
+        directory = "{root}/plot"
+        size = "1 MB"
+
+        [[farmer.plots]]
+        directory = "{root}/plot/nested"
+        size = "100 MB"
+
+        [node]
+        directory = "{root}/plot"
+        name = "farmer"
+        "#,
+        root = root.display()
+    ))
+    .unwrap();
+
+    let report = preflight(&config);
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert!(report.errors.iter().any(|error| error.contains("should be bigger than")));
+    assert!(report.errors.iter().any(|error| error.contains("cannot be inside the plot #0")));
+    assert!(report.errors.iter().any(|error| error.contains("cannot share the directory")));
+    let message = report.into_result().unwrap_err().to_string();
+    assert!(message.starts_with("found 4 error(s)"), "{message}");
+}
+
+#[test]
+fn unknown_extra_options() {
+    let known: toml::Table = toml::from_str(
+        r#"
+        name = "node"
+        [dsn]
+        listen_addresses = []
+        "#,
+    )
+    .unwrap();
+    let extra: toml::Table = toml::from_str(
+        r#"
+        name = "other"
+        nmae = "typo"
+        completely_unrelated = 1
+        [dsn]
+        listen_adresses = []
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        unknown_keys(&known, &extra, "node.advanced"),
+        vec![
+            ("node.advanced.completely_unrelated".to_owned(), None),
+            ("node.advanced.dsn.listen_adresses".to_owned(), Some("listen_addresses".to_owned())),
+            ("node.advanced.nmae".to_owned(), Some("name".to_owned())),
+        ]
+    );
+}
+
+#[test]
+fn unset_extra_options() {
+    #[derive(serde::Serialize, serde::Deserialize, Default)]
+    enum BlocksPruning {
+        #[default]
+        ArchiveCanonical,
+        Some(u32),
+    }
+    #[derive(serde::Serialize, serde::Deserialize, Default)]
+    struct Configuration {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blocks_pruning: Option<BlocksPruning>,
+    }
+
+    // valid options which are unset by default are not reported
+    let configuration = Configuration::default();
+    let extra = toml::toml! {
+        nmae = "typo"
+        [blocks_pruning]
+        Some = 256
+    };
+    let applied = apply_extra_options(&configuration, extra.clone()).unwrap();
+    assert!(applied.blocks_pruning.is_some());
+    assert_eq!(
+        unknown_options(&configuration, Some(&applied), &extra, "node.advanced"),
+        vec![("node.advanced.nmae".to_owned(), Some("name".to_owned()))]
+    );
+}
+
+#[test]
+fn config_migration_from_v0() {
+    let mut table: toml::Table = toml::from_str(
+        r#"
+        chain = "devnet"
+
+        [farmer]
+        reward_address = "5FWr7j9DW4uy7K1JLmFN2R3eoae35PFDUfW7G42ARpBEUaN7"
+        plot_directory = "/mnt/disk1"
+        plot_size = "10 GB"
+        cache_size = "2 GB"
+
+        [node]
+        directory = "/mnt/node"
+        name = "farmer"
+        executor = true
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(migrate(&mut table).unwrap(), Some(0));
+    assert_eq!(config_version(&table).unwrap(), CONFIG_VERSION);
+
+    let config: Config = toml::Value::Table(table).try_into().unwrap();
+    assert!(matches!(config.chain, ChainConfig::DevNet));
+    assert_eq!(
+        config.farmer.plots,
+        vec![PlotConfig { directory: PathBuf::from("/mnt/disk1"), size: ByteSize::gb(10) }]
+    );
+    assert_eq!(config.farmer.advanced.cache_size, ByteSize::gb(2));
+    assert!(config.node.advanced.executor);
+}
+
+#[test]
+fn config_migration_up_to_date() {
+    let mut table = toml::toml! {
+        version = 1
+        chain = "Gemini3d"
+    };
+    let before = table.clone();
+
+    assert_eq!(migrate(&mut table).unwrap(), None);
+    assert_eq!(table, before);
+}
+
+#[test]
+fn config_from_newer_version() {
+    let mut table = toml::Table::new();
+    table.insert("version".to_owned(), toml::Value::Integer(CONFIG_VERSION as i64 + 1));
+
+    assert!(migrate(&mut table).is_err());
+}
+
+#[test]
+fn relative_config_paths() {
+    let mut config: Config = toml::from_str(
+        r#"
+        version = 1
+        chain = { custom = "/media/farm/chainspec.json" }
+
+        [farmer]
+        reward_address = "5FWr7j9DW4uy7K1JLmFN2R3eoae35PFDUfW7G42ARpBEUaN7"
+        plots = [{ directory = "/media/farm/plots", size = "2 TB" }, { directory = "/mnt/disk2", size = "2 TB" }]
+        cache_directory = "/media/farm/cache"
+        summary_directory = "/media/farm"
+
+        [node]
+        directory = "/media/farm/node"
+        name = "farmer"
+        provider_storage_directory = "/media/farm/node/provider-storage"
+        "#,
+    )
+    .unwrap();
+
+    config.relativize_paths(Path::new("/media/farm"));
+    assert_eq!(config.farmer.plots[0].directory, PathBuf::from("plots"));
+    // paths outside of the root are kept as they are
+    assert_eq!(config.farmer.plots[1].directory, PathBuf::from("/mnt/disk2"));
+    assert_eq!(config.farmer.summary_directory, PathBuf::from("."));
+    assert_eq!(config.node.provider_storage_directory, PathBuf::from("node/provider-storage"));
+
+    // the farm is moved to another mount point
+    config.resolve_paths(Path::new("/mnt/usb"));
+    assert_eq!(config.farmer.plots[0].directory, PathBuf::from("/mnt/usb/plots"));
+    assert_eq!(config.farmer.plots[1].directory, PathBuf::from("/mnt/disk2"));
+    assert_eq!(config.farmer.cache_directory, PathBuf::from("/mnt/usb/cache"));
+    assert_eq!(config.farmer.summary_directory, PathBuf::from("/mnt/usb"));
+    assert_eq!(config.node.directory, PathBuf::from("/mnt/usb/node"));
+    assert!(matches!(
+        config.chain,
+        ChainConfig::Custom(CustomChainConfig { custom, .. }) if custom == Path::new("/mnt/usb/chainspec.json")
+    ));
+}
+
+#[test]
+fn custom_chain_config() {
+    #[derive(serde::Deserialize, serde::Serialize)]
+    struct Chain {
+        chain: ChainConfig,
+    }
+
+    let Chain { chain } = toml::from_str(
+        r#"
+        chain = { custom = "/path/to/chainspec.json", bootstrap_nodes = ["/ip4/127.0.0.1/tcp/30333"] }
+        "#,
+    )
+    .unwrap();
+    let ChainConfig::Custom(custom) = &chain else {
+        panic!("expected a custom chain, got {chain:?}");
+    };
+    assert_eq!(custom.custom, PathBuf::from("/path/to/chainspec.json"));
+    assert_eq!(custom.bootstrap_nodes, vec!["/ip4/127.0.0.1/tcp/30333"]);
+    assert!(custom.dsn_bootstrap_nodes.is_empty());
+
+    let serialized = toml::to_string(&Chain { chain }).unwrap();
+    assert!(matches!(
+        toml::from_str::<Chain>(&serialized).unwrap().chain,
+        ChainConfig::Custom(CustomChainConfig { custom, .. }) if custom == Path::new("/path/to/chainspec.json")
+    ));
+
+    assert_eq!(
+        toml::to_string(&Chain { chain: ChainConfig::DevNet }).unwrap(),
+        "chain = \"DevNet\"\n"
+    );
+    assert!(toml::from_str::<Chain>(r#"chain = "Unknown""#).is_err());
+    assert!(toml::from_str::<Chain>(r#"chain = { custom = "spec.json", typo = 1 }"#).is_err());
+}
+
+#[test]
+fn config_dotted_keys() {
+    let mut config = toml::Value::Table(toml::toml! {
+        [farmer]
+        plots = [{ directory = "/mnt/disk1", size = "1 GB" }]
+
+        [node.advanced]
+        executor = true
+    });
+
+    set_value(
+        &mut config,
+        "farmer.plots.0.size",
+        parse_value("farmer.plots.0.size", "2GB").unwrap(),
+    )
+    .unwrap();
+    set_value(&mut config, "farmer.plots.1.directory", "/mnt/disk2".into()).unwrap();
+    set_value(&mut config, "node.advanced.blocks_pruning.Some", parse_value("", "256").unwrap())
+        .unwrap();
+
+    assert_eq!(get_value(&config, "farmer.plots.0.size"), Some(&"2.0 GB".into()));
+    assert_eq!(get_value(&config, "farmer.plots.1.directory"), Some(&"/mnt/disk2".into()));
+    assert_eq!(get_value(&config, "node.advanced.blocks_pruning.Some"), Some(&256.into()));
+    assert_eq!(get_value(&config, "node.advanced.executor"), Some(&true.into()));
+    assert_eq!(get_value(&config, "node.name"), None);
+
+    assert!(set_value(&mut config, "farmer.plots.3.size", "1 GB".into()).is_err());
+    assert!(parse_value("farmer.plots.0.size", "1KB").is_err());
+    assert!(parse_value("farmer.summary_directory", "./not-existing-directory").is_err());
+    assert!(parse_value("farmer.cache_directory", "./not-existing-directory").is_err());
+    assert_eq!(
+        parse_value("farmer.summary_directory", "./").unwrap(),
+        toml::Value::try_from(Path::new("./")).unwrap()
+    );
+    assert!(parse_value("chain", "devv").is_err());
+    assert!(parse_value("version", "2").is_err());
+    assert_eq!(parse_value("node.advanced.extra_option", "not toml").unwrap(), "not toml".into());
+}
+
+#[test]
+fn layered_config() {
+    let file = toml::toml! {
+        chain = "Gemini3d"
+
+        [farmer]
+        plots = [
+            { directory = "/mnt/disk1", size = "1 GB" },
+            { directory = "/mnt/disk2", size = "1 GB" },
+        ]
+
+        [node]
+        name = "farmer"
+    };
+    let env = env_overrides([
+        ("SUBSPACE_CLI__FARMER__PLOTS__1__SIZE".to_owned(), "2TB".to_owned()),
+        ("SUBSPACE_CLI__NODE__NAME".to_owned(), "from-env".to_owned()),
+        ("SUBSPACE_CLI__NODE__ADVANCED__blocks_pruning__Some".to_owned(), "256".to_owned()),
+        ("SUBSPACE_CLI_PLOT_SIZE".to_owned(), "3TB".to_owned()),
+    ]);
+    let cli = vec![("node.name".to_owned(), "from-cli".to_owned(), ConfigSource::Cli)];
+    assert_eq!(env.len(), 3);
+
+    let (config, sources) = apply_layers(file, [env, cli]).unwrap();
+    let config = toml::Value::Table(config);
+
+    assert_eq!(get_value(&config, "farmer.plots.0.size"), Some(&"1 GB".into()));
+    assert_eq!(get_value(&config, "farmer.plots.1.size"), Some(&"2.0 TB".into()));
+    assert_eq!(get_value(&config, "farmer.plots.1.directory"), Some(&"/mnt/disk2".into()));
+    assert_eq!(get_value(&config, "node.name"), Some(&"from-cli".into()));
+    assert_eq!(get_value(&config, "node.advanced.blocks_pruning.Some"), Some(&256.into()));
+
+    assert_eq!(sources["chain"], ConfigSource::File);
+    assert_eq!(sources["farmer.plots.0.size"], ConfigSource::File);
+    assert_eq!(
+        sources["farmer.plots.1.size"],
+        ConfigSource::Env("SUBSPACE_CLI__FARMER__PLOTS__1__SIZE".to_owned())
+    );
+    assert_eq!(sources["node.name"], ConfigSource::Cli);
+
+    // an index past the end of the plots does not replace them
+    let file = toml::toml! {
+        [farmer]
+        plots = [{ directory = "/mnt/disk1", size = "1 GB" }]
+    };
+    let cli = vec![("farmer.plots.1.size".to_owned(), "2TB".to_owned(), ConfigSource::Cli)];
+    assert!(apply_layers(file, [cli]).is_err());
+}
+
+#[test]
+fn key_value_checker() {
+    assert_eq!(
+        key_value_parser("farmer.plots.0.size=2TB").unwrap(),
+        ("farmer.plots.0.size".to_owned(), "2TB".to_owned())
+    );
+    assert!(key_value_parser("farmer.plots.0.size").is_err());
+    assert!(key_value_parser("=2TB").is_err());
+}
+
 #[test]
 fn yes_no_checker() {
     assert!(yes_or_no_parser("yas").is_err());
@@ -111,6 +1044,68 @@ fn directory_checker() {
     assert!(directory_parser("./").is_ok());
 }
 
+#[test]
+fn existing_directory_checker() {
+    assert!(existing_directory_parser(Path::new("./"), false).is_ok());
+    assert!(existing_directory_parser(Path::new("./Cargo.toml"), true).is_err());
+    assert!(existing_directory_parser(Path::new("./not-existing-directory"), false).is_err());
+}
+
+#[test]
+fn profile_name_checker() {
+    assert!(profile_name_parser("testnet-2_a").is_ok());
+    assert!(profile_name_parser("").is_err());
+    assert!(profile_name_parser("../escape").is_err());
+    assert!(profile_name_parser("with space").is_err());
+}
+
+#[test]
+fn named_profile() {
+    let default = Profile::named(DEFAULT_PROFILE);
+    assert_eq!(default, Profile::default());
+    assert_eq!(default.namespace(PathBuf::from("subspace-cli")), PathBuf::from("subspace-cli"));
+    assert_eq!(default.instance_lock(), ".subspaceFarmer");
+    assert_eq!(default.args(), "");
+
+    let named = Profile::named("second");
+    assert_eq!(named.name(), "second");
+    assert_eq!(
+        named.namespace(PathBuf::from("subspace-cli")),
+        Path::new("subspace-cli").join("profiles").join("second")
+    );
+    assert_eq!(named.instance_lock(), ".subspaceFarmer-second");
+    assert_eq!(named.args(), " --profile second");
+
+    // farms of different config files are independent
+    let first = Profile::default().with_config_path(Some(PathBuf::from("/etc/farm1.toml")));
+    let second = Profile::default().with_config_path(Some(PathBuf::from("/etc/farm2.toml")));
+    assert_ne!(first.instance_lock(), default.instance_lock());
+    assert_ne!(first.instance_lock(), second.instance_lock());
+    let logs = PathBuf::from("logs");
+    assert!(first.farm_namespace(logs.clone()).starts_with(logs.join("configs")));
+    assert_ne!(first.farm_namespace(logs.clone()), second.farm_namespace(logs.clone()));
+    assert_eq!(default.farm_namespace(logs.clone()), logs);
+}
+
+#[test]
+fn profile_listing() {
+    let config_dir =
+        std::env::temp_dir().join(format!("subspace-cli-profiles-{}", rand::random::<u64>()));
+    assert!(profile_names(&config_dir, CONFIG_FILE).unwrap().is_empty());
+
+    for name in ["second", "first", "no-config"] {
+        std::fs::create_dir_all(config_dir.join("profiles").join(name)).unwrap();
+    }
+    for name in ["second", "first"] {
+        std::fs::write(config_dir.join("profiles").join(name).join(CONFIG_FILE), "").unwrap();
+    }
+    std::fs::write(config_dir.join(CONFIG_FILE), "").unwrap();
+
+    let names = profile_names(&config_dir, CONFIG_FILE).unwrap();
+    std::fs::remove_dir_all(&config_dir).unwrap();
+    assert_eq!(names, vec![DEFAULT_PROFILE, "first", "second"]);
+}
+
 #[test]
 fn node_name_checker() {
     assert!(node_name_parser("     ").is_err());
@@ -136,6 +1131,10 @@ fn size_checker() {
 fn chain_checker() {
     assert!(ChainConfig::from_str("gemini3d").is_ok());
     assert!(ChainConfig::from_str("devv").is_err());
+    assert!(matches!(
+        ChainConfig::from_str("./Cargo.toml"),
+        Ok(ChainConfig::Custom(CustomChainConfig { custom, .. })) if custom.is_absolute()
+    ));
 }
 
 #[test]
@@ -193,3 +1192,265 @@ fn custom_log_dir_test() {
     #[cfg(target_os = "windows")]
     assert!(log_path.ends_with("AppData/Local/subspace-cli/logs"));
 }
+
+#[test]
+fn farm_metrics() {
+    let metrics = Metrics::new().unwrap();
+    metrics.set_syncing(90, 100);
+    metrics.set_plotting(1, 256, 1024);
+    metrics.set_finalized_block(100);
+    metrics.set_summary(&Summary {
+        total_rewards: Rewards(1_500_000_000_000_000_000),
+        vote_count: 3,
+        authored_count: 2,
+        last_processed_block_num: 96,
+        ..Default::default()
+    });
+
+    let encoded = metrics.encode().unwrap();
+    for line in [
+        "subspace_cli_sync_position_blocks 90",
+        "subspace_cli_sync_target_blocks 100",
+        "subspace_cli_plotting_progress_ratio{plot=\"1\"} 0.25",
+        "subspace_cli_rewards 1.5",
+        "subspace_cli_votes 3",
+        "subspace_cli_authored_blocks 2",
+        "subspace_cli_last_processed_block 95",
+        "subspace_cli_finalized_block 100",
+        "subspace_cli_scanner_lag_blocks 5",
+    ] {
+        assert!(encoded.lines().any(|encoded| encoded == line), "`{line}` is missing:\n{encoded}");
+    }
+}
+
+#[test]
+fn metrics_config() {
+    #[derive(serde::Deserialize)]
+    struct Section {
+        metrics: Option<MetricsConfig>,
+    }
+
+    let Section { metrics } = toml::from_str("[metrics]").unwrap();
+    assert_eq!(metrics.unwrap().address, "127.0.0.1:9616".parse().unwrap());
+    let Section { metrics } = toml::from_str("metrics = { address = \"0.0.0.0:9000\" }").unwrap();
+    assert_eq!(metrics.unwrap().address, "0.0.0.0:9000".parse().unwrap());
+    let Section { metrics } = toml::from_str("").unwrap();
+    assert!(metrics.is_none());
+
+    assert_eq!(
+        parse_value("metrics.address", "0.0.0.0:9000").unwrap().as_str(),
+        Some("0.0.0.0:9000")
+    );
+    assert!(parse_value("metrics.address", "localhost").is_err());
+}
+
+#[test]
+fn farm_status() {
+    let status = Status::new(Metrics::new().unwrap());
+    assert_eq!(
+        status.health(),
+        Health { is_node_synced: false, is_farmer_built: false, is_alive: true }
+    );
+    assert!(!status.health().is_ready());
+
+    status.set_chain("Gemini 3d".to_owned());
+    status.set_syncing(90, 100);
+    status.set_synced();
+    assert!(!status.health().is_ready());
+    status.set_farmer_built();
+    assert!(status.health().is_ready());
+    assert!(status.health().is_alive);
+
+    status.set_plotting(1, Path::new("/mnt/disk2"), 512, 1024);
+    status.set_plotting(0, Path::new("/mnt/disk1"), 256, 1024);
+    status.set_plotting(0, Path::new("/mnt/disk1"), 1024, 1024);
+    status.set_finalized_block(100);
+    status.set_summary(&Summary { vote_count: 3, ..Default::default() });
+
+    let report = status.report();
+    assert_eq!(report.chain.as_deref(), Some("Gemini 3d"));
+    assert_eq!((report.node.sync_position, report.node.sync_target), (Some(90), Some(100)));
+    assert_eq!(report.node.finalized_block, Some(100));
+    assert_eq!(
+        report.plots,
+        vec![
+            PlotStatus {
+                id: 0,
+                directory: PathBuf::from("/mnt/disk1"),
+                plotted_bytes: 1024,
+                allocated_bytes: 1024,
+            },
+            PlotStatus {
+                id: 1,
+                directory: PathBuf::from("/mnt/disk2"),
+                plotted_bytes: 512,
+                allocated_bytes: 1024,
+            },
+        ]
+    );
+    assert_eq!(report.summary.as_ref().map(|summary| summary.vote_count), Some(3));
+
+    let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(json["node"]["is_synced"], true);
+    assert_eq!(json["summary"]["total_rewards"], "0");
+
+    // the farm is no longer alive once the rewards are no longer counted
+    status.set_solution_subscription_ended();
+    assert!(status.health().is_ready());
+    assert!(!status.health().is_alive);
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn control_socket() {
+    let dir = std::env::temp_dir().join(format!("subspace-cli-control-{}", rand::random::<u64>()));
+    let path = dir.join("farm.sock");
+    assert!(send_request(&path, &ControlRequest::Status).await.unwrap().is_none());
+
+    let status = Status::new(Metrics::new().unwrap());
+    status.set_chain("Gemini 3d".to_owned());
+    let stop = std::sync::Arc::new(tokio::sync::Notify::new());
+    let (socket, server) = listen(&path, Control::new(status, stop.clone())).unwrap();
+    tokio::spawn(server);
+
+    let Some(ControlResponse::Status(report)) =
+        send_request(&path, &ControlRequest::Status).await.unwrap()
+    else {
+        panic!("the farm should respond with its status");
+    };
+    assert_eq!(report.chain.as_deref(), Some("Gemini 3d"));
+    // the farmer cannot pause its plotting, which the farm says clearly
+    assert_eq!(
+        serde_json::from_str::<ControlRequest>(r#"{"command":"pause-plotting"}"#).unwrap(),
+        ControlRequest::PausePlotting
+    );
+    assert!(matches!(
+        send_request(&path, &ControlRequest::PausePlotting).await.unwrap(),
+        Some(ControlResponse::Error(err)) if err == PAUSE_PLOTTING_UNSUPPORTED
+    ));
+    assert!(matches!(
+        send_request(&path, &ControlRequest::Stop).await.unwrap(),
+        Some(ControlResponse::Done)
+    ));
+    // the farm is notified, even if it was not waiting yet
+    stop.notified().await;
+
+    assert_eq!(
+        serde_json::from_str::<ControlRequest>(r#"{"command":"set-log-level","filter":"debug"}"#)
+            .unwrap(),
+        ControlRequest::SetLogLevel { filter: "debug".to_owned() }
+    );
+
+    // the socket is removed along with the farm
+    drop(socket);
+    assert!(!path.exists());
+    assert!(send_request(&path, &ControlRequest::Status).await.unwrap().is_none());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn farm_events() {
+    assert_eq!(OutputFormat::select(Some(OutputFormat::Json), false), OutputFormat::Json);
+    assert_eq!(OutputFormat::select(None, true), OutputFormat::Text);
+
+    let token = Token { decimals: 18, symbol: "tSSC".to_owned() };
+    let record = |kind, amount| LedgerRecord {
+        block_number: 7,
+        block_hash: "0x07".to_owned(),
+        timestamp: None,
+        kind,
+        amount,
+    };
+    assert_eq!(
+        FarmEvent::from_record(&record(RewardKind::VoteReward, 1_500_000_000_000_000_000), &token),
+        FarmEvent::Reward {
+            block_number: 7,
+            block_hash: "0x07".to_owned(),
+            kind: "vote_reward".to_owned(),
+            amount: "1500000000000000000".to_owned(),
+            amount_tokens: "1.5".to_owned(),
+            symbol: "tSSC".to_owned(),
+        }
+    );
+    let vote = FarmEvent::from_record(&record(RewardKind::Vote, 0), &token);
+    assert_eq!(vote, FarmEvent::Vote { block_number: 7, block_hash: "0x07".to_owned() });
+
+    assert_eq!(
+        serde_json::to_value(&vote).unwrap(),
+        serde_json::json!({ "event": "vote", "block_number": 7, "block_hash": "0x07" })
+    );
+    assert_eq!(
+        serde_json::to_value(FarmEvent::PlottingFinished { plot: 1 }).unwrap(),
+        serde_json::json!({ "event": "plotting_finished", "plot": 1 })
+    );
+    assert_eq!(
+        serde_json::to_value(FarmEvent::Shutdown).unwrap(),
+        serde_json::json!({ "event": "shutdown" })
+    );
+}
+
+#[test]
+fn systemd_status() {
+    let status = Status::new(Metrics::new().unwrap());
+    assert_eq!(status_line(&status.report()), "Syncing");
+    status.set_syncing(90, 100);
+    assert_eq!(status_line(&status.report()), "Syncing: #90 of #100");
+    status.set_synced();
+    assert_eq!(status_line(&status.report()), "Starting the farmer");
+
+    status.set_farmer_built();
+    status.set_plotting(0, Path::new("/mnt/disk1"), 1024, 1024);
+    status.set_plotting(1, Path::new("/mnt/disk2"), 0, 1024);
+    assert_eq!(status_line(&status.report()), "Plotting: 50.00%");
+    status.set_plotting(1, Path::new("/mnt/disk2"), 1024, 1024);
+    assert_eq!(status_line(&status.report()), "Farming");
+
+    let token = Token { decimals: 18, symbol: "tSSC".to_owned() };
+    status.set_summary(&Summary {
+        authored_count: 1,
+        vote_count: 3,
+        total_rewards: Rewards(1_500_000_000_000_000_000),
+        token: Some(token),
+        ..Default::default()
+    });
+    assert_eq!(
+        status_line(&status.report()),
+        "Farming: 1.5 tSSC earned, farmed 1 block(s), and voted on 3 block(s)"
+    );
+}
+
+#[test]
+fn systemd_unit() {
+    assert_eq!(unit_name(DEFAULT_PROFILE), "subspace-cli.service");
+    assert_eq!(unit_name("second"), "subspace-cli-second.service");
+
+    let args = farm_args(
+        Path::new("/usr/local/bin/subspace-cli"),
+        Some(Path::new("/home/farmer/my farm/settings.toml")),
+        Some(Path::new("/mnt/100%")),
+    )
+    .unwrap();
+    assert_eq!(args[args.len() - 3..], ["farm", "--output", "json"]);
+
+    let unit = render_unit(&args, Some("farmer"));
+    assert!(unit.contains(
+        "\nExecStart=/usr/local/bin/subspace-cli --config \"/home/farmer/my farm/settings.toml\" \
+         --data-dir /mnt/100%% farm --output json\n"
+    ));
+    assert!(unit.contains("\nType=notify\n"));
+    assert!(unit.contains("\nUser=farmer\n"));
+    assert!(unit.contains("\nWantedBy=multi-user.target\n"));
+
+    let args = farm_args(Path::new("/usr/local/bin/subspace-cli"), None, None).unwrap();
+    assert_eq!(args, ["/usr/local/bin/subspace-cli", "farm", "--output", "json"]);
+    let unit = render_unit(&args, None);
+    assert!(!unit.contains("User="));
+    assert!(unit.contains("\nWantedBy=default.target\n"));
+
+    // under `sudo`, the unit runs as the user who ran it
+    assert_eq!(unit_user(None, Some("farmer".to_owned())), "farmer");
+    assert_eq!(unit_user(Some("other".to_owned()), Some("farmer".to_owned())), "other");
+    assert_eq!(unit_user(None, Some("root".to_owned())), whoami::username());
+    assert_eq!(unit_user(None, None), whoami::username());
+}
@@ -1,10 +1,16 @@
+use std::convert::Infallible;
 use std::env;
 use std::fs::create_dir_all;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::OnceLock;
 
+use chrono::NaiveDate;
 use color_eyre::eyre::{eyre, Context, Result};
 use futures::prelude::*;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
 use owo_colors::OwoColorize;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use subspace_sdk::{ByteSize, PublicKey};
@@ -14,14 +20,22 @@ use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::prelude::*;
-use tracing_subscriber::{fmt, EnvFilter, Layer};
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer};
 
 use crate::config::MIN_PLOT_SIZE;
+use crate::home::{data_dir, data_dir_or, profile_home};
+use crate::profile::profile;
 use crate::summary::Rewards;
 
 /// for how long a log file should be valid
 const KEEP_LAST_N_FILE: usize = 7;
 
+/// replaces the filters of the logs installed by [`install_tracing`]
+type LogFilterReloader = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+/// set by [`install_tracing`]
+static LOG_FILTER_RELOADER: OnceLock<LogFilterReloader> = OnceLock::new();
+
 /// <3
 pub(crate) fn print_ascii_art() {
     println!("
@@ -46,7 +60,9 @@ pub(crate) fn print_version() {
 pub(crate) fn print_run_executable_command() {
     let exec_name =
         std::env::args().next().map(PathBuf::from).expect("First argument always exists");
-    println!("`{exec_name:?} farm`");
+    let data_dir_arg =
+        data_dir().map(|data_dir| format!(" --data-dir {data_dir:?}")).unwrap_or_default();
+    println!("`{exec_name:?}{data_dir_arg}{} farm`", profile().args());
 }
 
 /// gets the input from the user for a given `prompt`
@@ -119,6 +135,23 @@ pub(crate) fn directory_parser(location: &str) -> Result<PathBuf> {
     }
 }
 
+/// the provided path should be an existing directory, the user is never
+/// prompted, and the directory is only created if `create_missing` is set
+pub(crate) fn existing_directory_parser(path: &Path, create_missing: bool) -> Result<PathBuf> {
+    if path.is_dir() {
+        return Ok(path.to_owned());
+    }
+    if path.exists() {
+        return Err(eyre!("supplied path `{}` is not a directory!", path.display()));
+    }
+    if !create_missing {
+        return Err(eyre!("supplied directory `{}` does not exist!", path.display()));
+    }
+    create_dir_all(path)
+        .wrap_err_with(|| format!("could not create the directory: `{}`", path.display()))?;
+    Ok(path.to_owned())
+}
+
 /// utilize `ByteSize` crate for the validation
 pub(crate) fn size_parser(size: &str) -> Result<ByteSize> {
     let Ok(size) = size.parse::<ByteSize>() else {
@@ -131,6 +164,38 @@ pub(crate) fn size_parser(size: &str) -> Result<ByteSize> {
     }
 }
 
+/// parses the size of the piece cache
+pub(crate) fn cache_size_parser(size: &str) -> Result<ByteSize> {
+    size.parse::<ByteSize>().map_err(|err| eyre!("could not parse the value: {err}"))
+}
+
+/// formats the given amount, in the smallest unit of the token, as a decimal
+/// number of tokens with the given number of decimals
+///
+/// integer arithmetic is used, so the amount is never rounded
+pub(crate) fn format_amount(amount: u128, decimals: u8) -> String {
+    // `u128` has at most 39 digits, more decimals only add leading zeros
+    let Some(unit) = 10u128.checked_pow(decimals.into()) else {
+        let fraction = format!("{amount:0width$}", width = decimals as usize);
+        return match fraction.trim_end_matches('0') {
+            "" => "0".to_owned(),
+            fraction => format!("0.{fraction}"),
+        };
+    };
+    let (whole, fraction) = (amount / unit, amount % unit);
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{fraction:0width$}", width = decimals as usize);
+    format!("{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+/// parses a date in the `YYYY-MM-DD` format
+pub(crate) fn date_parser(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| eyre!("could not parse the date, please use the `YYYY-MM-DD` format"))
+}
+
 pub(crate) fn yes_or_no_parser(answer: &str) -> Result<bool> {
     match answer.to_lowercase().as_str() {
         "y" | "yes" => Ok(true),
@@ -159,11 +224,26 @@ pub(crate) fn provider_storage_dir_getter() -> PathBuf {
 }
 
 fn data_dir_getter() -> PathBuf {
-    dirs::data_dir().expect("data folder must be present in every major OS").join("subspace-cli")
+    profile().namespace(data_dir_or(|| {
+        dirs::data_dir()
+            .expect("data folder must be present in every major OS")
+            .join("subspace-cli")
+    }))
 }
 
-/// returns OS specific log directory
+/// returns the path of the control socket of the running farm, see
+/// [`crate::control`]
+pub(crate) fn control_socket_getter() -> PathBuf {
+    profile().farm_namespace(data_dir_getter()).join("farm.sock")
+}
+
+/// returns OS specific log directory, or the `logs` directory of the profile
+/// in the data directory if it is supplied
 pub(crate) fn custom_log_dir() -> PathBuf {
+    if let Some(profile_home) = profile_home() {
+        return profile().farm_namespace(profile_home.join("logs"));
+    }
+
     let id = "subspace-cli";
 
     #[cfg(target_os = "macos")]
@@ -178,7 +258,10 @@ pub(crate) fn custom_log_dir() -> PathBuf {
     let path = dirs::data_local_dir().map(|dir| dir.join(id).join("logs"));
     // evaluates to: `C:/Users/Username/AppData/Local/${id}/logs/
 
-    path.expect("Could not resolve custom log directory path!")
+    let profile = profile();
+    profile.farm_namespace(
+        profile.namespace(path.expect("Could not resolve custom log directory path!")),
+    )
 }
 
 /// opens log directory
@@ -229,13 +312,24 @@ pub(crate) fn install_tracing(is_verbose: bool) {
         .build(log_dir)
         .expect("building should always succeed");
 
-    // filter for logging
+    // filter for logging, which can be replaced while farming (i.e. with
+    // `set-log-level`)
     let filter = || {
         EnvFilter::builder()
             .with_default_directive(LevelFilter::INFO.into())
             .from_env_lossy()
             .add_directive("regalloc2=off".parse().expect("hardcoded value is true"))
     };
+    let (file_filter, file_filter_handle) = reload::Layer::new(filter());
+    let (stdout_filter, stdout_filter_handle) = reload::Layer::new(filter());
+    let _ = LOG_FILTER_RELOADER.set(Box::new(move |directives| {
+        file_filter_handle
+            .reload(log_filter(directives)?)
+            .context("couldn't reload the log filter")?;
+        stdout_filter_handle
+            .reload(log_filter(directives)?)
+            .context("couldn't reload the log filter")
+    }));
 
     // start logger, after we acquire the bundle identifier
     #[cfg(tokio_unstable)]
@@ -248,23 +342,33 @@ pub(crate) fn install_tracing(is_verbose: bool) {
         .with(
             BunyanFormattingLayer::new("subspace-cli".to_owned(), file_appender)
                 .and_then(JsonStorageLayer)
-                .with_filter(filter()),
+                .with_filter(file_filter),
         )
         .with(ErrorLayer::default());
 
     // if verbose, then also print to stdout
-    if is_verbose {
-        tracing_layer
-            .with(
-                fmt::layer()
-                    .with_ansi(!cfg!(windows))
-                    .with_span_events(FmtSpan::CLOSE)
-                    .with_filter(filter()),
-            )
-            .init();
-    } else {
-        tracing_layer.init();
-    }
+    let stdout_layer = is_verbose.then(|| {
+        fmt::layer()
+            .with_ansi(!cfg!(windows))
+            .with_span_events(FmtSpan::CLOSE)
+            .with_filter(stdout_filter)
+    });
+    tracing_layer.with(stdout_layer).init();
+}
+
+/// parses the given filter of the logs (i.e. `debug`, or
+/// `info,subspace_cli=debug`)
+fn log_filter(directives: &str) -> Result<EnvFilter> {
+    let filter = EnvFilter::builder()
+        .parse(directives)
+        .wrap_err_with(|| format!("`{directives}` is not a valid log filter"))?;
+    Ok(filter.add_directive("regalloc2=off".parse().expect("hardcoded value is true")))
+}
+
+/// replaces the filter of the logs installed by [`install_tracing`]
+pub(crate) fn set_log_filter(directives: &str) -> Result<()> {
+    let reload = LOG_FILTER_RELOADER.get().ok_or_else(|| eyre!("logging is not installed"))?;
+    reload(directives)
 }
 
 pub fn is_default<T: Default + PartialEq>(t: &T) -> bool {
@@ -275,33 +379,95 @@ pub fn apply_extra_options<T: serde::Serialize + serde::de::DeserializeOwned>(
     config: &T,
     extra: toml::Table,
 ) -> Result<T> {
-    fn apply_extra_options_inner(config: &mut toml::Table, extra: toml::Table) {
-        for (k, v) in extra {
-            use toml::Value::Table;
+    let mut table: toml::Table =
+        toml::from_str(&toml::to_string(config).expect("Config is always toml serializable"))
+            .expect("Config is always toml deserializable");
+
+    merge_tables(&mut table, extra).context("Failed to merge extra options")?;
+
+    toml::from_str(&toml::to_string(&table).context("Failed to serialize extra options")?)
+        .context("Failed to deserialize extra options")
+}
 
-            let e = match config.get_mut(&k) {
-                Some(e) => e,
+/// deep merges `extra` into `config`, values in `extra` take precedence
+///
+/// tables are merged recursively, and a table with index keys (i.e. `0`, `1`)
+/// merges into the elements of an existing list, every other value is
+/// replaced
+///
+/// fails if an index is past the end of its list
+pub(crate) fn merge_tables(config: &mut toml::Table, extra: toml::Table) -> Result<()> {
+    use toml::Value::{Array, Table};
+
+    fn merge_tables_at(config: &mut toml::Table, extra: toml::Table, path: &str) -> Result<()> {
+        for (k, v) in extra {
+            let key = if path.is_empty() { k.clone() } else { format!("{path}.{k}") };
+            match config.get_mut(&k) {
+                Some(e) => merge_values(e, v, &key)?,
                 None => {
                     config.insert(k, v);
-                    continue;
                 }
-            };
-
-            match (e, v) {
-                (Table(table), Table(v)) => apply_extra_options_inner(table, v),
-                (entry, v) => *entry = v,
             }
         }
+        Ok(())
     }
 
-    let mut table: toml::Table =
-        toml::from_str(&toml::to_string(config).expect("Config is always toml serializable"))
-            .expect("Config is always toml deserializable");
+    fn merge_values(entry: &mut toml::Value, v: toml::Value, key: &str) -> Result<()> {
+        match (entry, v) {
+            (Table(table), Table(v)) => merge_tables_at(table, v, key)?,
+            (Array(array), Table(v)) if v.keys().all(|k| k.parse::<usize>().is_ok()) =>
+                for (k, v) in v {
+                    let index = k.parse::<usize>().expect("checked in the guard above");
+                    let len = array.len();
+                    let element = array.get_mut(index).ok_or_else(|| {
+                        eyre!("`{key}.{k}` is out of range, `{key}` has {len} element(s)")
+                    })?;
+                    merge_values(element, v, &format!("{key}.{k}"))?;
+                },
+            (entry, v) => *entry = v,
+        }
+        Ok(())
+    }
 
-    apply_extra_options_inner(&mut table, extra);
+    merge_tables_at(config, extra, "")
+}
+
+/// parses `KEY=VALUE` pairs given on the command line
+pub(crate) fn key_value_parser(pair: &str) -> Result<(String, String)> {
+    match pair.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() =>
+            Ok((key.trim().to_owned(), value.trim().to_owned())),
+        _ => Err(eyre!("`{pair}` should be in the form of `KEY=VALUE`")),
+    }
+}
+
+/// binds to the given address, and returns a local HTTP server answering the
+/// requests with `respond`
+///
+/// binding is done right away, so that a taken address is reported before
+/// farming starts
+pub(crate) fn serve_http<F>(
+    address: SocketAddr,
+    name: &'static str,
+    respond: F,
+) -> Result<impl Future<Output = Result<()>>>
+where
+    F: Fn(&Request<Body>) -> Response<Body> + Clone + Send + Sync + 'static,
+{
+    let make_service = make_service_fn(move |_| {
+        let respond = respond.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = respond(&request);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&address)
+        .wrap_err_with(|| format!("couldn't serve the {name} at `{address}`"))?
+        .serve(make_service);
 
-    Ok(toml::from_str(&toml::to_string(&table).context("Failed to deserialize extra options")?)
-        .expect("At this stage we know that config is always toml deserializable"))
+    Ok(async move { server.await.wrap_err_with(|| format!("{name} server failed")) })
 }
 
 #[cfg(tokio_unstable)]